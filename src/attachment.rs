@@ -0,0 +1,273 @@
+//! Attachments read from files, memory or readers.
+//!
+//! [`EmailBuilder::attachment`](crate::EmailBuilder::attachment) accepts a
+//! path or an [`AttachmentSource`], so generated files can be attached
+//! without writing them to disk first:
+//!
+//! ```no_run
+//! use mailkit::{AttachmentSource, Email};
+//!
+//! # fn run(pdf: Vec<u8>) -> Result<(), mailkit::MailkitError> {
+//! let email = Email::builder()
+//!     .to("rcpt@example.com")
+//!     .text("Your invoice is attached.")
+//!     .attachment("terms.pdf")
+//!     .attachment(AttachmentSource::bytes(pdf, "invoice-42.pdf"))
+//!     .attachment(AttachmentSource::bytes("id,total\n42,9.99\n", "export.csv").content_type("text/csv"))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use lettre::message::SinglePart;
+use lettre::message::header::{ContentDisposition, ContentType};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::email_sender::MailkitError;
+
+/// How mail clients should present an attachment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Disposition {
+    /// Offered as a download (`Content-Disposition: attachment`).
+    #[default]
+    Attachment,
+    /// Shown in the message body where the client supports it
+    /// (`Content-Disposition: inline`).
+    Inline,
+}
+
+/// What to do when an attachment's content does not match the type its
+/// file name suggests, e.g. a PDF named `report.png`.
+///
+/// The content is checked for PDF, PNG, JPEG, GIF, ZIP, Office documents,
+/// iCalendar, CSV and plain text. Text content only conflicts with binary
+/// types such as images or PDFs. Files without a known extension always
+/// get the detected type, and an explicit
+/// [`AttachmentSource::content_type`] is never second-guessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MimePolicy {
+    /// Keep the type from the file name.
+    TrustExtension,
+    /// Use the type detected from the content.
+    #[default]
+    TrustContent,
+    /// Fail the send with [`MailkitError::Validation`].
+    RejectMismatch,
+}
+
+impl FromStr for MimePolicy {
+    type Err = MailkitError;
+
+    /// Parses the values accepted by `MAILKIT_MIME_POLICY`: `extension`,
+    /// `content` and `reject`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "extension" | "trust-extension" => Ok(MimePolicy::TrustExtension),
+            "content" | "trust-content" => Ok(MimePolicy::TrustContent),
+            "reject" | "reject-mismatch" => Ok(MimePolicy::RejectMismatch),
+            other => Err(MailkitError::Validation(format!("Invalid MIME policy: {}", other))),
+        }
+    }
+}
+
+/// Where an attachment's content comes from, with its file name, content
+/// type and disposition.
+///
+/// Files are read when the message is built. Readers are drained when the
+/// source is created, so the source can be cloned and sent more than once,
+/// e.g. in bulk sends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachmentSource {
+    content: Content,
+    filename: Option<String>,
+    content_type: Option<String>,
+    disposition: Disposition,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Content {
+    Path(String),
+    Bytes(Vec<u8>),
+}
+
+impl AttachmentSource {
+    /// The file at `path`, named after its last component.
+    pub fn path<P: Into<String>>(path: P) -> Self {
+        Self::new(Content::Path(path.into()), None)
+    }
+
+    /// Content held in memory, e.g. a generated PDF.
+    pub fn bytes<D: Into<Vec<u8>>, N: Into<String>>(data: D, filename: N) -> Self {
+        Self::new(Content::Bytes(data.into()), Some(filename.into()))
+    }
+
+    /// Reads `reader` to the end.
+    pub fn from_reader<R: Read, N: Into<String>>(mut reader: R, filename: N) -> Result<Self, MailkitError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(Self::bytes(data, filename))
+    }
+
+    /// Reads `reader` to the end without blocking the runtime.
+    pub async fn from_async_reader<R: AsyncRead + Unpin, N: Into<String>>(
+        mut reader: R,
+        filename: N,
+    ) -> Result<Self, MailkitError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        Ok(Self::bytes(data, filename))
+    }
+
+    fn new(content: Content, filename: Option<String>) -> Self {
+        Self {
+            content,
+            filename,
+            content_type: None,
+            disposition: Disposition::Attachment,
+        }
+    }
+
+    /// Overrides the file name shown to the recipient.
+    pub fn filename<N: Into<String>>(mut self, filename: N) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    /// Overrides the content type, which is otherwise taken from the file
+    /// name and checked against the content, see [`MimePolicy`].
+    pub fn content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Defaults to [`Disposition::Attachment`].
+    pub fn disposition(mut self, disposition: Disposition) -> Self {
+        self.disposition = disposition;
+        self
+    }
+
+    /// The file name used in the message.
+    pub fn name(&self) -> &str {
+        self.filename
+            .as_deref()
+            .or_else(|| match &self.content {
+                Content::Path(path) => Path::new(path).file_name().and_then(|n| n.to_str()),
+                Content::Bytes(_) => None,
+            })
+            .unwrap_or("attachment")
+    }
+
+    /// The file the content is read from, if it is not held in memory.
+    pub fn file_path(&self) -> Option<&str> {
+        match &self.content {
+            Content::Path(path) => Some(path),
+            Content::Bytes(_) => None,
+        }
+    }
+
+    /// Whether the disposition is [`Disposition::Inline`].
+    pub fn is_inline(&self) -> bool {
+        self.disposition == Disposition::Inline
+    }
+
+    /// The content type override or the one guessed from the file name,
+    /// before the content is inspected.
+    pub fn mime_type(&self) -> String {
+        match &self.content_type {
+            Some(content_type) => content_type.clone(),
+            None => mime_guess::from_path(self.name())
+                .first_or_octet_stream()
+                .essence_str()
+                .to_string(),
+        }
+    }
+
+    /// Checks the overrides before anything is read.
+    pub(crate) fn validate(&self) -> Result<(), MailkitError> {
+        if self.name().trim().is_empty() {
+            return Err(MailkitError::Validation("Attachment file name is empty".into()));
+        }
+        parse_content_type(&self.mime_type())?;
+        Ok(())
+    }
+
+    /// Builds the MIME part, reading a file from disk.
+    pub(crate) fn part(&self, policy: MimePolicy) -> Result<SinglePart, MailkitError> {
+        match &self.content {
+            Content::Path(path) => self.build(std::fs::read(path)?, policy),
+            Content::Bytes(data) => self.build(data.clone(), policy),
+        }
+    }
+
+    /// Async version of [`AttachmentSource::part`] using `tokio::fs`.
+    pub(crate) async fn part_async(&self, policy: MimePolicy) -> Result<SinglePart, MailkitError> {
+        match &self.content {
+            Content::Path(path) => self.build(tokio::fs::read(path).await?, policy),
+            Content::Bytes(data) => self.build(data.clone(), policy),
+        }
+    }
+
+    fn build(&self, data: Vec<u8>, policy: MimePolicy) -> Result<SinglePart, MailkitError> {
+        let disposition = match self.disposition {
+            Disposition::Attachment => ContentDisposition::attachment(self.name()),
+            Disposition::Inline => ContentDisposition::inline_with_name(self.name()),
+        };
+        let content_type = parse_content_type(&self.resolve_type(&data, policy)?)?;
+        Ok(SinglePart::builder()
+            .header(disposition)
+            .header(content_type)
+            .body(data))
+    }
+
+    /// Picks the content type from the override, the file name and the
+    /// content.
+    fn resolve_type(&self, data: &[u8], policy: MimePolicy) -> Result<String, MailkitError> {
+        if let Some(content_type) = &self.content_type {
+            return Ok(content_type.clone());
+        }
+        let guessed = mime_guess::from_path(self.name())
+            .first()
+            .map(|m| m.essence_str().to_string())
+            .filter(|m| m != "application/octet-stream");
+        let sniffed = crate::sniff::sniff(data);
+        Ok(match (guessed, sniffed) {
+            (None, sniffed) => sniffed.unwrap_or("application/octet-stream").to_string(),
+            (Some(guessed), Some(sniffed)) if !crate::sniff::compatible(&guessed, sniffed) => match policy {
+                MimePolicy::TrustExtension => guessed,
+                MimePolicy::TrustContent => {
+                    crate::warn!("Attachment {} looks like {}, not {}", self.name(), sniffed, guessed);
+                    sniffed.to_string()
+                }
+                MimePolicy::RejectMismatch => {
+                    return Err(MailkitError::Validation(format!(
+                        "Attachment {} looks like {}, not {}",
+                        self.name(),
+                        sniffed,
+                        guessed
+                    )));
+                }
+            },
+            (Some(guessed), _) => guessed,
+        })
+    }
+}
+
+impl From<String> for AttachmentSource {
+    fn from(path: String) -> Self {
+        Self::path(path)
+    }
+}
+
+impl From<&str> for AttachmentSource {
+    fn from(path: &str) -> Self {
+        Self::path(path)
+    }
+}
+
+pub(crate) fn parse_content_type(value: &str) -> Result<ContentType, MailkitError> {
+    ContentType::parse(value).map_err(|_| MailkitError::Validation(format!("Invalid content type: {}", value)))
+}
@@ -0,0 +1,199 @@
+//! Results of sending one message to many recipients.
+//!
+//! [`EmailSender::send_bulk`](crate::EmailSender::send_bulk),
+//! [`EmailSender::deliver_bulk`](crate::EmailSender::deliver_bulk) and
+//! their async versions return a [`BulkReport`] with one
+//! [`RecipientResult`] per recipient instead of stopping at the first
+//! failure.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::email_sender::{Delivery, MailkitError};
+
+/// What a bulk send does after a recipient fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BulkMode {
+    /// Keep sending to the remaining recipients.
+    #[default]
+    ContinueOnError,
+    /// Skip every recipient not yet sent to. Async sends already in
+    /// progress are still finished and reported as sent or failed; set
+    /// [`max_in_flight`](crate::EmailSenderBuilder::max_in_flight) to bound
+    /// how many that can be.
+    StopOnFirstError,
+}
+
+/// Outcome for a single recipient.
+#[derive(Debug)]
+pub enum Outcome {
+    Sent(Delivery),
+    /// Building or delivering the message failed.
+    Failed(MailkitError),
+    /// The recipient's template or subject could not be rendered, so
+    /// nothing was sent.
+    RenderFailed(MailkitError),
+    /// Not sent, because of [`BulkMode::StopOnFirstError`] or because a
+    /// message with the same idempotency key was already sent to this
+    /// recipient.
+    Skipped,
+}
+
+impl Outcome {
+    /// The error of a failed or unrendered message.
+    pub fn error(&self) -> Option<&MailkitError> {
+        match self {
+            Outcome::Failed(err) | Outcome::RenderFailed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Result<Delivery, MailkitError>> for Outcome {
+    fn from(result: Result<Delivery, MailkitError>) -> Self {
+        match result {
+            Ok(delivery) => Outcome::Sent(delivery),
+            Err(MailkitError::Duplicate(_)) => Outcome::Skipped,
+            Err(err) => Outcome::Failed(err),
+        }
+    }
+}
+
+/// A recipient of a bulk send and what happened to its message.
+#[derive(Debug)]
+pub struct RecipientResult {
+    pub(crate) recipient: String,
+    pub(crate) outcome: Outcome,
+}
+
+impl RecipientResult {
+    pub fn recipient(&self) -> &str {
+        &self.recipient
+    }
+
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+
+    pub fn delivery(&self) -> Option<&Delivery> {
+        match &self.outcome {
+            Outcome::Sent(delivery) => Some(delivery),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&MailkitError> {
+        self.outcome.error()
+    }
+}
+
+/// Per-recipient results of a bulk send, in the order recipients were
+/// given.
+#[derive(Debug, Default)]
+pub struct BulkReport {
+    pub(crate) results: Vec<RecipientResult>,
+    pub(crate) elapsed: Duration,
+}
+
+impl BulkReport {
+    pub fn results(&self) -> &[RecipientResult] {
+        &self.results
+    }
+
+    pub fn into_results(self) -> Vec<RecipientResult> {
+        self.results
+    }
+
+    pub fn total(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn sent(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Sent(_)))
+    }
+
+    /// Recipients whose message failed to build or deliver.
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Failed(_)))
+    }
+
+    /// Recipients whose template could not be rendered.
+    pub fn render_failed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::RenderFailed(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Skipped))
+    }
+
+    /// Whether no recipient failed. Recipients skipped as duplicates
+    /// count as success; [`BulkMode::StopOnFirstError`] only skips after a
+    /// failure.
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Recipients that failed to render or send, with their errors.
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &MailkitError)> {
+        self.results
+            .iter()
+            .filter_map(|r| r.error().map(|e| (r.recipient(), e)))
+    }
+
+    /// Wall-clock time of the whole bulk send.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    fn count(&self, f: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|r| f(&r.outcome)).count()
+    }
+}
+
+/// Stops a streaming bulk send.
+///
+/// Clones share state, so keep one clone and pass another to
+/// [`EmailSender::deliver_stream`](crate::EmailSender::deliver_stream) or
+/// [`EmailSender::send_template_stream`](crate::EmailSender::send_template_stream).
+/// After [`cancel`](CancelToken::cancel) no further recipients are taken
+/// from the input; sends already in progress finish and their results are
+/// still yielded before the stream ends.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once [`cancel`](CancelToken::cancel) has been called.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
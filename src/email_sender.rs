@@ -7,17 +7,32 @@
 //! file attachment reads using `tokio::fs`.
 
 use std::env;
-use std::fs;
-use tokio::fs as tokio_fs;
-use std::path::{Path, PathBuf};
-use std::time::Duration;
-use lettre::AsyncTransport;
-use lettre::message::{Attachment, Mailbox, Message, MultiPart, SinglePart};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::dkim::DkimConfig as DkimSigner;
+use lettre::message::{Mailbox, Message, MultiPart, SinglePart};
 use lettre::transport::smtp::authentication::Credentials;
-use lettre::{AsyncSmtpTransport, SmtpTransport, Tokio1Executor, Transport};
-use mime_guess;
+use lettre::transport::smtp::client::{Certificate, TlsParameters};
+use lettre::transport::smtp::extension::ClientId;
+use std::sync::Arc;
 use tera::{Context, Tera};
 
+use crate::attachment::{AttachmentSource, MimePolicy};
+use crate::bulk::{BulkMode, BulkReport, CancelToken, Outcome, RecipientResult};
+use crate::recipients::Recipient;
+use crate::dkim::DkimConfig;
+use crate::idempotency::{IdempotencyStore, MemoryStore};
+use crate::inline::InlineImage;
+use crate::limit::{Limiter, RateLimit};
+use crate::outbox::{Outbox, OutboxWorker};
+use crate::pool::PoolConfig;
+use crate::retry::{RetryClass, RetryPolicy};
+use crate::transport::smtp::SmtpSettings;
+use crate::transport::{AsyncTransport, SmtpTransport, Transport, TransportConfig};
+
+pub use crate::transport::TlsMode;
+
 use std::error::Error as StdError;
 use std::fmt;
 #[derive(Debug)]
@@ -29,6 +44,14 @@ pub enum MailkitError {
     Build(lettre::error::Error),
     Address(lettre::address::AddressError),
     MissingEnvVar(&'static str),
+    /// `sendmail` exited unsuccessfully. `status` is `None` when it was
+    /// killed by a signal.
+    Sendmail { status: Option<i32>, stderr: String },
+    /// A message with this idempotency key was already sent within the
+    /// sender's idempotency window, so it was skipped.
+    Duplicate(String),
+    /// A DKIM key could not be loaded or the DKIM settings are invalid.
+    Dkim(String),
 }
 
 impl fmt::Display for MailkitError {
@@ -41,6 +64,18 @@ impl fmt::Display for MailkitError {
             MailkitError::Build(err) => write!(f, "Build message error: {}", err),
             MailkitError::Address(err) => write!(f, "Address parse error: {}", err),
             MailkitError::MissingEnvVar(var) => write!(f, "Missing environment variable: {}", var),
+            MailkitError::Sendmail { status, stderr } => {
+                match status {
+                    Some(code) => write!(f, "sendmail exited with status {}", code)?,
+                    None => write!(f, "sendmail was terminated by a signal")?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+            MailkitError::Duplicate(key) => write!(f, "Duplicate message: idempotency key {} was already sent", key),
+            MailkitError::Dkim(msg) => write!(f, "DKIM error: {}", msg),
         }
     }
 }
@@ -56,262 +91,1680 @@ impl StdError for MailkitError {
             _ => None,
         }
     }
-}
+}
+
+/// Stable, machine-readable category of a [`MailkitError`], e.g. for
+/// metrics labels or alert routing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Validation,
+    Io,
+    Smtp,
+    Template,
+    Build,
+    Address,
+    MissingEnvVar,
+    Sendmail,
+    Duplicate,
+    Dkim,
+}
+
+impl ErrorKind {
+    /// Lowercase name such as `"smtp"` or `"missing_env_var"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Validation => "validation",
+            ErrorKind::Io => "io",
+            ErrorKind::Smtp => "smtp",
+            ErrorKind::Template => "template",
+            ErrorKind::Build => "build",
+            ErrorKind::Address => "address",
+            ErrorKind::MissingEnvVar => "missing_env_var",
+            ErrorKind::Sendmail => "sendmail",
+            ErrorKind::Duplicate => "duplicate",
+            ErrorKind::Dkim => "dkim",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl MailkitError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            MailkitError::Validation(_) => ErrorKind::Validation,
+            MailkitError::Io(_) => ErrorKind::Io,
+            MailkitError::Smtp(_) => ErrorKind::Smtp,
+            MailkitError::Tera(_) => ErrorKind::Template,
+            MailkitError::Build(_) => ErrorKind::Build,
+            MailkitError::Address(_) => ErrorKind::Address,
+            MailkitError::MissingEnvVar(_) => ErrorKind::MissingEnvVar,
+            MailkitError::Sendmail { .. } => ErrorKind::Sendmail,
+            MailkitError::Duplicate(_) => ErrorKind::Duplicate,
+            MailkitError::Dkim(_) => ErrorKind::Dkim,
+        }
+    }
+
+    /// Whether the same message may succeed later: `4xx` replies,
+    /// timeouts, refused or reset connections and sendmail's `EX_TEMPFAIL`.
+    /// These are the failures a [`RetryPolicy`] can retry.
+    pub fn is_transient(&self) -> bool {
+        RetryClass::of(self).is_some()
+    }
+
+    /// Whether retrying cannot help: `5xx` replies, invalid addresses or
+    /// configuration, template and message build errors, and duplicates.
+    ///
+    /// Some failures, such as TLS handshake errors, are neither transient
+    /// nor permanent.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            MailkitError::Smtp(e) => e.is_permanent(),
+            MailkitError::Io(_) => false,
+            MailkitError::Sendmail { status, .. } => status.is_some() && !self.is_transient(),
+            MailkitError::Validation(_)
+            | MailkitError::Tera(_)
+            | MailkitError::Build(_)
+            | MailkitError::Address(_)
+            | MailkitError::MissingEnvVar(_)
+            | MailkitError::Duplicate(_)
+            | MailkitError::Dkim(_) => true,
+        }
+    }
+
+    /// The SMTP reply code, e.g. `550`, if the server rejected the message.
+    pub fn smtp_code(&self) -> Option<u16> {
+        match self {
+            MailkitError::Smtp(e) => e.status().map(u16::from),
+            _ => None,
+        }
+    }
+
+    /// The text of the rejecting SMTP reply without the code, e.g.
+    /// `"5.1.1 No such user"`.
+    pub fn response_text(&self) -> Option<String> {
+        match self {
+            MailkitError::Smtp(e) if e.status().is_some() => e.source().map(|s| s.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The RFC 3463 enhanced status code at the start of the reply text,
+    /// e.g. `(5, 1, 1)` for `"5.1.1 No such user"`.
+    pub fn enhanced_status(&self) -> Option<(u8, u16, u16)> {
+        let text = self.response_text()?;
+        let token = text.split_whitespace().next()?;
+        let mut parts = token.split('.');
+        let class: u8 = parts.next()?.parse().ok()?;
+        let subject: u16 = parts.next()?.parse().ok()?;
+        let detail: u16 = parts.next()?.parse().ok()?;
+        if parts.next().is_some() || !matches!(class, 2 | 4 | 5) || subject > 999 || detail > 999 {
+            return None;
+        }
+        Some((class, subject, detail))
+    }
+}
+
+impl From<std::io::Error> for MailkitError {
+    fn from(e: std::io::Error) -> Self {
+        MailkitError::Io(e)
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for MailkitError {
+    fn from(e: lettre::transport::smtp::Error) -> Self {
+        MailkitError::Smtp(e)
+    }
+}
+
+impl From<tera::Error> for MailkitError {
+    fn from(e: tera::Error) -> Self {
+        MailkitError::Tera(e)
+    }
+}
+
+impl From<lettre::error::Error> for MailkitError {
+    fn from(e: lettre::error::Error) -> Self {
+        MailkitError::Build(e)
+    }
+}
+
+impl From<lettre::address::AddressError> for MailkitError {
+    fn from(e: lettre::address::AddressError) -> Self {
+        MailkitError::Address(e)
+    }
+}
+
+/// An email message that can be built, inspected and stored before it is
+/// handed to [`EmailSender::deliver`].
+///
+/// Use [`Email::builder`] to construct one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Email {
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+    reply_to: Vec<String>,
+    subject: String,
+    text: Option<String>,
+    html: Option<String>,
+    text_from_html: bool,
+    attachments: Vec<AttachmentSource>,
+    inline_images: Vec<InlineImage>,
+    headers: Vec<(String, String)>,
+    idempotency_key: Option<String>,
+}
+
+impl Email {
+    pub fn builder() -> EmailBuilder {
+        EmailBuilder::default()
+    }
+
+    /// A copy addressed to `recipient` only. An idempotency key is
+    /// extended with the recipient so each copy is tracked separately.
+    pub(crate) fn with_to(&self, recipient: &str) -> Email {
+        Email {
+            to: vec![recipient.to_string()],
+            idempotency_key: self.idempotency_key.as_ref().map(|k| format!("{}:{}", k, recipient)),
+            ..self.clone()
+        }
+    }
+
+    pub fn to(&self) -> &[String] {
+        &self.to
+    }
+
+    pub fn cc(&self) -> &[String] {
+        &self.cc
+    }
+
+    pub fn bcc(&self) -> &[String] {
+        &self.bcc
+    }
+
+    pub fn reply_to(&self) -> &[String] {
+        &self.reply_to
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Plain-text body, if any.
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// HTML body, if any. With a text body as well, the message carries
+    /// both as `multipart/alternative`.
+    pub fn html(&self) -> Option<&str> {
+        self.html.as_deref()
+    }
+
+    /// The explicit text body, or one generated from the HTML if
+    /// [`EmailBuilder::text_from_html`] was used.
+    ///
+    /// The sender also generates one when
+    /// [`EmailSenderBuilder::auto_text`] is on, wrapped at its
+    /// [`text_width`](EmailSenderBuilder::text_width).
+    pub fn text_alternative(&self) -> Option<String> {
+        match (&self.text, &self.html) {
+            (Some(text), _) => Some(text.clone()),
+            (None, Some(html)) if self.text_from_html => Some(crate::html_text::html_to_text(html)),
+            _ => None,
+        }
+    }
+
+    /// The files attached to this email.
+    pub fn attachments(&self) -> &[AttachmentSource] {
+        &self.attachments
+    }
+
+    /// Images embedded in the HTML body.
+    pub fn inline_images(&self) -> &[InlineImage] {
+        &self.inline_images
+    }
+
+    /// Extra headers added to the message, in insertion order.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+}
+
+/// Builder for [`Email`].
+#[derive(Debug, Clone, Default)]
+pub struct EmailBuilder {
+    email: Email,
+}
+
+impl EmailBuilder {
+    pub fn to<S: Into<String>>(mut self, addr: S) -> Self {
+        self.email.to.push(addr.into());
+        self
+    }
+
+    pub fn cc<S: Into<String>>(mut self, addr: S) -> Self {
+        self.email.cc.push(addr.into());
+        self
+    }
+
+    pub fn bcc<S: Into<String>>(mut self, addr: S) -> Self {
+        self.email.bcc.push(addr.into());
+        self
+    }
+
+    pub fn reply_to<S: Into<String>>(mut self, addr: S) -> Self {
+        self.email.reply_to.push(addr.into());
+        self
+    }
+
+    pub fn subject<S: Into<String>>(mut self, subject: S) -> Self {
+        self.email.subject = subject.into();
+        self
+    }
+
+    pub fn text<S: Into<String>>(mut self, body: S) -> Self {
+        self.email.text = Some(body.into());
+        self
+    }
+
+    pub fn html<S: Into<String>>(mut self, body: S) -> Self {
+        self.email.html = Some(body.into());
+        self
+    }
+
+    /// Generates the text alternative from the HTML body when no text body
+    /// is set, even if the sender's
+    /// [`auto_text`](EmailSenderBuilder::auto_text) is off.
+    pub fn text_from_html(mut self) -> Self {
+        self.email.text_from_html = true;
+        self
+    }
+
+    /// Attaches a file path or an [`AttachmentSource`].
+    pub fn attachment<A: Into<AttachmentSource>>(mut self, attachment: A) -> Self {
+        self.email.attachments.push(attachment.into());
+        self
+    }
+
+    /// Embeds the image at `path`, shown by the HTML body with
+    /// `<img src="cid:{content_id}">`. The content type is guessed from the
+    /// file extension.
+    pub fn inline_image<C: Into<String>, P: Into<String>>(mut self, content_id: C, path: P) -> Self {
+        self.email
+            .inline_images
+            .push(InlineImage::from_path(content_id.into(), path.into()));
+        self
+    }
+
+    /// Embeds an image held in memory, see [`EmailBuilder::inline_image`].
+    pub fn inline_image_bytes<C: Into<String>, T: Into<String>>(
+        mut self,
+        content_id: C,
+        data: Vec<u8>,
+        content_type: T,
+    ) -> Self {
+        self.email
+            .inline_images
+            .push(InlineImage::from_bytes(content_id.into(), data, content_type.into()));
+        self
+    }
+
+    /// Adds a raw header such as `X-Campaign`.
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.email.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Key identifying this message across retries, e.g. an order id. See
+    /// [`crate::idempotency`].
+    pub fn idempotency_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.email.idempotency_key = Some(key.into());
+        self
+    }
+
+    /// Finishes the email. At least one of `to`, `cc` or `bcc` must be set.
+    pub fn build(self) -> Result<Email, MailkitError> {
+        let email = self.email;
+        if email.to.is_empty() && email.cc.is_empty() && email.bcc.is_empty() {
+            return Err(MailkitError::Validation("Email has no recipients".into()));
+        }
+        for (name, _) in &email.headers {
+            let valid = !name.is_empty()
+                && name.bytes().all(|b| b.is_ascii_graphic() && b != b':');
+            if !valid {
+                return Err(MailkitError::Validation(format!("Invalid header name: {}", name)));
+            }
+        }
+        if !email.inline_images.is_empty() && email.html.is_none() {
+            return Err(MailkitError::Validation("Inline images need an HTML body".into()));
+        }
+        for image in &email.inline_images {
+            image.validate()?;
+        }
+        for attachment in &email.attachments {
+            attachment.validate()?;
+        }
+        Ok(email)
+    }
+}
+
+/// A message accepted by the transport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    message_id: String,
+    response: String,
+    elapsed: Duration,
+}
+
+impl Delivery {
+    /// The `Message-ID` header, including angle brackets.
+    pub fn message_id(&self) -> &str {
+        &self.message_id
+    }
+
+    /// What the transport reported, e.g. `"250 2.0.0 Ok: queued as 4F2A"`.
+    pub fn response(&self) -> &str {
+        &self.response
+    }
+
+    /// Time from building the message to its acceptance, retries included.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Splits a `---` delimited front matter block off a rendered template and
+/// returns its `subject` with the rest of the document.
+fn split_front_matter(rendered: &str) -> Result<(Option<String>, &str), String> {
+    let Some(rest) = rendered
+        .strip_prefix("---\n")
+        .or_else(|| rendered.strip_prefix("---\r\n"))
+    else {
+        return Ok((None, rendered));
+    };
+    let mut subject = None;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim();
+        if line == "---" {
+            return Ok((subject, &rest[offset..]));
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("invalid front matter line: {}", line))?;
+        // other keys are left for other tools
+        if key.trim().eq_ignore_ascii_case("subject") {
+            // values in HTML templates are autoescaped
+            subject = Some(one_line(&crate::html_text::decode_entities(value)));
+        }
+    }
+    Err("front matter is not closed with ---".into())
+}
+
+/// Collapses whitespace and line breaks, for use as a header value.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Name the subject of a mail merge is registered under in its own copy
+/// of the sender's Tera instance.
+const MERGE_SUBJECT: &str = "__mailkit_merge_subject";
+
+/// A compiled mail merge subject, or why it failed to compile.
+struct MergeSubject(Result<Tera, String>);
+
+/// Where [`EmailSender`] loads its Tera templates from.
+enum TemplateSource {
+    None,
+    Dir(PathBuf),
+    Tera(Box<Tera>),
+}
+
+/// Message body: a single part, or text and HTML alternatives and the
+/// HTML's inline images.
+enum Body {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+impl Body {
+    fn into_message(self, builder: lettre::message::MessageBuilder) -> Result<Message, MailkitError> {
+        Ok(match self {
+            Body::Single(part) => builder.singlepart(part)?,
+            Body::Multi(part) => builder.multipart(part)?,
+        })
+    }
+
+    /// A `multipart/mixed` with the body first, ready for attachments.
+    fn into_mixed(self) -> MultiPart {
+        match self {
+            Body::Single(part) => MultiPart::mixed().singlepart(part),
+            Body::Multi(part) => MultiPart::mixed().multipart(part),
+        }
+    }
+}
+
+/// Builder for [`EmailSender`] with every setting made explicit.
+///
+/// ```no_run
+/// use mailkit::EmailSender;
+/// use std::time::Duration;
+///
+/// let sender = EmailSender::builder()
+///     .from("noreply@example.com")
+///     .host("smtp.example.com")
+///     .port(587)
+///     .username("apikey")
+///     .password("secret")
+///     .connect_timeout(Duration::from_secs(5))
+///     .template_dir("templates")
+///     .build()
+///     .unwrap();
+/// ```
+pub struct EmailSenderBuilder {
+    from: Option<String>,
+    host: Option<String>,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    connect_timeout: Duration,
+    command_timeout: Duration,
+    helo_name: Option<String>,
+    tls: Option<TlsMode>,
+    root_certificates: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    pool: PoolConfig,
+    retry: RetryPolicy,
+    max_in_flight: Option<usize>,
+    rate_limits: Vec<RateLimit>,
+    outbox: Option<Outbox>,
+    idempotency_store: Arc<dyn IdempotencyStore>,
+    idempotency_window: Duration,
+    dkim: Option<DkimConfig>,
+    auto_text: bool,
+    text_width: usize,
+    mime_policy: MimePolicy,
+    transport: Option<(Arc<dyn Transport>, Arc<dyn AsyncTransport>)>,
+    templates: TemplateSource,
+    validate_emails: bool,
+}
+
+impl Default for EmailSenderBuilder {
+    fn default() -> Self {
+        Self {
+            from: None,
+            host: None,
+            port: 587,
+            username: None,
+            password: None,
+            connect_timeout: Duration::from_secs(10),
+            command_timeout: Duration::from_secs(30),
+            helo_name: None,
+            tls: None,
+            root_certificates: Vec::new(),
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            pool: PoolConfig::default(),
+            retry: RetryPolicy::none(),
+            max_in_flight: None,
+            rate_limits: Vec::new(),
+            outbox: None,
+            idempotency_store: Arc::new(MemoryStore::new()),
+            idempotency_window: Duration::from_secs(24 * 60 * 60),
+            dkim: None,
+            auto_text: true,
+            text_width: crate::html_text::DEFAULT_WIDTH,
+            mime_policy: MimePolicy::default(),
+            transport: None,
+            templates: TemplateSource::None,
+            validate_emails: true,
+        }
+    }
+}
+
+impl EmailSenderBuilder {
+    /// Address used in the `From` header.
+    pub fn from<S: Into<String>>(mut self, addr: S) -> Self {
+        self.from = Some(addr.into());
+        self
+    }
+
+    /// SMTP server host name.
+    pub fn host<S: Into<String>>(mut self, host: S) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// SMTP server port. Defaults to 587.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Login name for SMTP AUTH. Defaults to the `From` address when only
+    /// a password is set.
+    pub fn username<S: Into<String>>(mut self, username: S) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    pub fn password<S: Into<String>>(mut self, password: S) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Sets both the connect and the command timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.connect_timeout(timeout).command_timeout(timeout)
+    }
+
+    /// Time allowed for the TCP connect, TLS handshake and greeting.
+    /// Defaults to 10 seconds.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Time allowed for each SMTP command once connected. Defaults to 30
+    /// seconds.
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = timeout;
+        self
+    }
+
+    /// Name sent with `EHLO`. Defaults to `localhost`.
+    pub fn helo_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.helo_name = Some(name.into());
+        self
+    }
+
+    /// How the connection is secured. Defaults to [`TlsMode::Wrapper`] on
+    /// port 465 and [`TlsMode::StartTlsRequired`] otherwise.
+    pub fn tls(mut self, mode: TlsMode) -> Self {
+        self.tls = Some(mode);
+        self
+    }
+
+    /// Trusts an additional PEM encoded root certificate, e.g. the CA of a
+    /// staging relay.
+    pub fn add_root_certificate<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Accepts any server certificate. Only use this against test relays.
+    pub fn accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Accepts certificates issued for a different host name.
+    pub fn accept_invalid_hostnames(mut self, accept: bool) -> Self {
+        self.accept_invalid_hostnames = accept;
+        self
+    }
+
+    /// Connection reuse settings. See [`PoolConfig`].
+    pub fn pool(mut self, config: PoolConfig) -> Self {
+        self.pool = config;
+        self
+    }
+
+    /// Retries transient delivery failures. See [`RetryPolicy`]; by
+    /// default nothing is retried.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Maximum number of messages handed to the transport at the same time,
+    /// across all send methods. Unlimited by default, although bulk and
+    /// stream methods and the outbox worker start at most 16 sends at once.
+    ///
+    /// Sync sends block their thread while waiting for a slot. Called on a
+    /// current-thread tokio runtime that stalls the runtime, so use the
+    /// async methods there.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Adds a token bucket limit, e.g. [`RateLimit::per_second`]`(10)`.
+    /// Call repeatedly to combine limits such as 10 per second and 500 per
+    /// day.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limits.push(limit);
+        self
+    }
+
+    /// Delivers through `transport` instead of SMTP. The SMTP settings of
+    /// this builder are then ignored.
+    pub fn transport<T: Transport + AsyncTransport + 'static>(mut self, transport: T) -> Self {
+        let transport = Arc::new(transport);
+        self.transport = Some((transport.clone(), transport));
+        self
+    }
+
+    /// Selects a built-in transport. [`TransportConfig::Smtp`] switches
+    /// back to SMTP after an earlier [`transport`](Self::transport) call.
+    pub fn transport_config(mut self, config: TransportConfig) -> Self {
+        self.transport = config.build();
+        self
+    }
+
+    /// Loads every template below `dir`.
+    pub fn template_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.templates = TemplateSource::Dir(dir.into());
+        self
+    }
+
+    /// Uses an already configured Tera instance.
+    pub fn tera(mut self, tera: Tera) -> Self {
+        self.templates = TemplateSource::Tera(Box::new(tera));
+        self
+    }
+
+    /// Spool directory for [`EmailSender::enqueue`]. It is created, and
+    /// messages left by a crashed worker are requeued, when the sender is
+    /// built.
+    pub fn outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = Some(outbox);
+        self
+    }
+
+    /// Where idempotency keys are recorded. Defaults to a [`MemoryStore`].
+    pub fn idempotency_store<S: IdempotencyStore + 'static>(mut self, store: S) -> Self {
+        self.idempotency_store = Arc::new(store);
+        self
+    }
+
+    /// How long an idempotency key blocks duplicates after its message was
+    /// sent. Defaults to 24 hours.
+    pub fn idempotency_window(mut self, window: Duration) -> Self {
+        self.idempotency_window = window;
+        self
+    }
+
+    /// Whether HTML messages without a text body get one generated from
+    /// the HTML, see [`crate::html_text`]. Defaults to `true`.
+    pub fn auto_text(mut self, enabled: bool) -> Self {
+        self.auto_text = enabled;
+        self
+    }
+
+    /// Line width of generated text bodies; `0` disables wrapping.
+    /// Defaults to 78.
+    pub fn text_width(mut self, width: usize) -> Self {
+        self.text_width = width;
+        self
+    }
+
+    /// How attachments whose content does not match their file name are
+    /// handled. Defaults to [`MimePolicy::TrustContent`].
+    pub fn mime_policy(mut self, policy: MimePolicy) -> Self {
+        self.mime_policy = policy;
+        self
+    }
+
+    /// Signs every message with DKIM.
+    pub fn dkim(mut self, config: DkimConfig) -> Self {
+        self.dkim = Some(config);
+        self
+    }
+
+    /// Whether addresses are checked with the built-in validator before
+    /// sending. Defaults to `true`.
+    pub fn validate_emails(mut self, validate: bool) -> Self {
+        self.validate_emails = validate;
+        self
+    }
+
+    /// Builds the sender, reporting every misconfigured setting at once in a
+    /// [`MailkitError::Validation`].
+    pub fn build(self) -> Result<EmailSender, MailkitError> {
+        let mut problems = Vec::new();
+
+        let from = match &self.from {
+            None => {
+                problems.push("from address is not set".to_string());
+                String::new()
+            }
+            Some(addr) if self.validate_emails => match EmailSender::check_email(addr) {
+                Ok(addr) => addr,
+                Err(_) => {
+                    problems.push(format!("invalid from address: {}", addr));
+                    addr.clone()
+                }
+            },
+            Some(addr) => addr.clone(),
+        };
+        let (transport, async_transport, smtp) = match self.transport {
+            Some((transport, async_transport)) => (transport, async_transport, None),
+            None => {
+                let settings = Self::smtp_settings(
+                    SmtpOptions {
+                        host: self.host,
+                        port: self.port,
+                        username: self.username,
+                        password: self.password,
+                        connect_timeout: self.connect_timeout,
+                        command_timeout: self.command_timeout,
+                        helo_name: self.helo_name,
+                        tls: self.tls,
+                        root_certificates: self.root_certificates,
+                        accept_invalid_certs: self.accept_invalid_certs,
+                        accept_invalid_hostnames: self.accept_invalid_hostnames,
+                    },
+                    &from,
+                    &mut problems,
+                );
+                let smtp = Arc::new(SmtpTransport::new(settings, self.pool));
+                (smtp.clone() as Arc<dyn Transport>, smtp.clone() as Arc<dyn AsyncTransport>, Some(smtp))
+            }
+        };
+        let mut tera = match self.templates {
+            TemplateSource::None => Tera::default(),
+            TemplateSource::Tera(tera) => *tera,
+            TemplateSource::Dir(dir) => {
+                match Tera::new(&dir.join("**/*").to_string_lossy()) {
+                    Ok(tera) => tera,
+                    Err(err) => {
+                        problems.push(format!("failed to load templates from {}: {}", dir.display(), err));
+                        Tera::default()
+                    }
+                }
+            }
+        };
+        crate::inline::register(&mut tera);
+
+        let dkim = match self.dkim.as_ref().map(DkimConfig::signer).transpose() {
+            Ok(signer) => signer,
+            Err(err) => {
+                problems.push(err.to_string());
+                None
+            }
+        };
+        if let Some(outbox) = &self.outbox
+            && let Err(err) = outbox.open()
+        {
+            problems.push(format!("failed to open outbox {}: {}", outbox.dir().display(), err));
+        }
+
+        if !problems.is_empty() {
+            let msg = problems.join("; ");
+            crate::error!("Invalid EmailSender configuration: {}", msg);
+            return Err(MailkitError::Validation(msg));
+        }
+
+        crate::info!("EmailSender initialized for {}", from);
+
+        Ok(EmailSender {
+            user_email: from,
+            transport,
+            async_transport,
+            smtp,
+            retry: self.retry,
+            limiter: Limiter::new(self.max_in_flight, &self.rate_limits),
+            outbox: self.outbox,
+            idempotency_store: self.idempotency_store,
+            idempotency_window: self.idempotency_window,
+            dkim,
+            auto_text: self.auto_text,
+            text_width: self.text_width,
+            mime_policy: self.mime_policy,
+            validate_emails: self.validate_emails,
+            tera,
+        })
+    }
+
+    /// Validates the SMTP part of the configuration, recording problems
+    /// instead of stopping at the first one.
+    fn smtp_settings(opts: SmtpOptions, from: &str, problems: &mut Vec<String>) -> SmtpSettings {
+        let host = match opts.host {
+            Some(host) if !host.trim().is_empty() => host,
+            _ => {
+                problems.push("SMTP host is not set".to_string());
+                String::new()
+            }
+        };
+        if opts.port == 0 {
+            problems.push("SMTP port must not be 0".to_string());
+        }
+        let credentials = match (opts.username, opts.password) {
+            (Some(_), None) => {
+                problems.push("username is set without a password".to_string());
+                None
+            }
+            (username, Some(password)) => Some(Credentials::new(
+                username.unwrap_or_else(|| from.to_string()),
+                password,
+            )),
+            (None, None) => None,
+        };
+        if opts.connect_timeout.is_zero() {
+            problems.push("connect timeout must be greater than zero".to_string());
+        }
+        if opts.command_timeout.is_zero() {
+            problems.push("command timeout must be greater than zero".to_string());
+        }
+        let hello_name = match opts.helo_name {
+            Some(name) if name.is_empty() || name.contains(char::is_whitespace) => {
+                problems.push(format!("invalid HELO name: {:?}", name));
+                ClientId::default()
+            }
+            Some(name) => ClientId::Domain(name),
+            None => ClientId::default(),
+        };
+        let tls = opts.tls.unwrap_or(if opts.port == 465 {
+            TlsMode::Wrapper
+        } else {
+            TlsMode::StartTlsRequired
+        });
+        let mut tls_builder = TlsParameters::builder(host.clone())
+            .dangerous_accept_invalid_certs(opts.accept_invalid_certs)
+            .dangerous_accept_invalid_hostnames(opts.accept_invalid_hostnames);
+        for (i, pem) in opts.root_certificates.iter().enumerate() {
+            if !String::from_utf8_lossy(pem).contains("-----BEGIN CERTIFICATE-----") {
+                problems.push(format!("root certificate #{} contains no PEM certificate", i + 1));
+                continue;
+            }
+            match Certificate::from_pem(pem) {
+                Ok(cert) => tls_builder = tls_builder.add_root_certificate(cert),
+                Err(err) => problems.push(format!("invalid root certificate #{}: {}", i + 1, err)),
+            }
+        }
+        let tls_parameters = match tls_builder.build() {
+            Ok(params) => Some(params),
+            Err(_) if host.is_empty() => None,
+            Err(err) => {
+                problems.push(format!("invalid TLS configuration: {}", err));
+                None
+            }
+        };
+
+        SmtpSettings {
+            host,
+            port: opts.port,
+            credentials,
+            connect_timeout: opts.connect_timeout,
+            command_timeout: opts.command_timeout,
+            hello_name,
+            tls,
+            tls_parameters,
+        }
+    }
+}
+
+/// SMTP fields of [`EmailSenderBuilder`], moved out for validation.
+struct SmtpOptions {
+    host: Option<String>,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    connect_timeout: Duration,
+    command_timeout: Duration,
+    helo_name: Option<String>,
+    tls: Option<TlsMode>,
+    root_certificates: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+}
+
+/// Main entry point for sending emails.
+pub struct EmailSender {
+    user_email: String,
+    transport: Arc<dyn Transport>,
+    async_transport: Arc<dyn AsyncTransport>,
+    smtp: Option<Arc<SmtpTransport>>,
+    retry: RetryPolicy,
+    limiter: Limiter,
+    outbox: Option<Outbox>,
+    idempotency_store: Arc<dyn IdempotencyStore>,
+    idempotency_window: Duration,
+    dkim: Option<DkimSigner>,
+    auto_text: bool,
+    text_width: usize,
+    mime_policy: MimePolicy,
+    validate_emails: bool,
+    tera: Tera,
+}
+
+impl EmailSender {
+    pub fn builder() -> EmailSenderBuilder {
+        EmailSenderBuilder::default()
+    }
+
+    /// Creates a sender authenticating as `user_email`.
+    ///
+    /// Templates are loaded from `MAILKIT_TEMPLATE_DIR` (default
+    /// `templates`). Prefer [`EmailSender::builder`] for new code.
+    pub fn new<U: Into<String>>(
+        user_email: U,
+        smtp_server: U,
+        user_password: U,
+        port: u16,
+        timeout_secs: u64,
+        validate_emails: bool,
+    ) -> Result<Self, MailkitError> {
+        let template_dir = env::var("MAILKIT_TEMPLATE_DIR").unwrap_or_else(|_| "templates".to_owned());
+
+        Self::builder()
+            .from(user_email)
+            .host(smtp_server)
+            .password(user_password)
+            .port(port)
+            .timeout(Duration::from_secs(timeout_secs))
+            .template_dir(template_dir)
+            .validate_emails(validate_emails)
+            .build()
+    }
+
+    /// Creates a sender from `EMAIL`, `SMTP_SERVER`, `EMAIL_PASSWORD` and
+    /// the optional `SMTP_PORT`, `SMTP_TLS`, `SMTP_USERNAME`,
+    /// `SMTP_HELO_NAME` and `MAILKIT_TEMPLATE_DIR` variables.
+    ///
+    /// `MAILKIT_TRANSPORT` selects the transport (see [`TransportConfig`]);
+    /// only `EMAIL` is required when it is not `smtp`.
+    pub fn from_env() -> Result<Self, MailkitError> {
+        let user_email = env::var("EMAIL").map_err(|_| MailkitError::MissingEnvVar("EMAIL"))?;
+        let transport = match env::var("MAILKIT_TRANSPORT") {
+            Ok(spec) => spec.parse()?,
+            Err(_) => TransportConfig::Smtp,
+        };
+        let template_dir = env::var("MAILKIT_TEMPLATE_DIR").unwrap_or_else(|_| "templates".to_owned());
+
+        let mut builder = Self::builder().from(user_email).template_dir(template_dir);
+        if let Some(dkim) = DkimConfig::from_env()? {
+            builder = builder.dkim(dkim);
+        }
+        if let Ok(policy) = env::var("MAILKIT_MIME_POLICY") {
+            builder = builder.mime_policy(policy.parse()?);
+        }
+        if transport != TransportConfig::Smtp {
+            return builder.transport_config(transport).build();
+        }
+
+        let server = env::var("SMTP_SERVER").map_err(|_| MailkitError::MissingEnvVar("SMTP_SERVER"))?;
+        let password = env::var("EMAIL_PASSWORD").map_err(|_| MailkitError::MissingEnvVar("EMAIL_PASSWORD"))?;
+        let port_str = env::var("SMTP_PORT").unwrap_or_else(|_| "587".into());
+        let port = port_str
+            .parse()
+            .map_err(|_| MailkitError::Validation("Invalid SMTP_PORT".into()))?;
+
+        let mut builder = builder.host(server).password(password).port(port);
+        if let Ok(username) = env::var("SMTP_USERNAME") {
+            builder = builder.username(username);
+        }
+        if let Ok(name) = env::var("SMTP_HELO_NAME") {
+            builder = builder.helo_name(name);
+        }
+        if let Ok(mode) = env::var("SMTP_TLS") {
+            builder = builder.tls(mode.parse()?);
+        }
+        builder.build()
+    }
+
+    pub(crate) fn check_email(addr: &str) -> Result<String, MailkitError> {
+        fn is_valid_email(addr: &str) -> bool {
+            let mut parts = addr.split('@');
+            if let (Some(local), Some(domain), None) = (parts.next(), parts.next(), parts.next()) {
+                !local.is_empty() && !domain.is_empty() && domain.contains('.')
+            } else {
+                false
+            }
+        }
+
+        if is_valid_email(addr) {
+            Ok(addr.to_lowercase())
+        } else {
+            crate::error!("Invalid email address: {}", addr);
+            Err(MailkitError::Validation(addr.into()))
+        }
+    }
+
+    fn parse_mailboxes(&self, addrs: &[String]) -> Result<Vec<Mailbox>, MailkitError> {
+        addrs
+            .iter()
+            .map(|addr| {
+                let addr = if self.validate_emails {
+                    Self::check_email(addr)?
+                } else {
+                    addr.clone()
+                };
+                Ok(addr.parse()?)
+            })
+            .collect()
+    }
+
+    /// `<unique@domain>` using the domain of the sender address.
+    fn new_message_id(&self) -> String {
+        let domain = self.user_email.rsplit_once('@').map_or("localhost", |(_, d)| d);
+        format!("<{}.{:x}@{}>", crate::transport::file::unique_id(), crate::retry::random(), domain)
+    }
+
+    fn create_base_message(
+        &self,
+        email: &Email,
+    ) -> Result<lettre::message::MessageBuilder, MailkitError> {
+        let mut builder = Message::builder()
+            .from(self.user_email.parse::<Mailbox>()?)
+            .message_id(Some(self.new_message_id()))
+            .subject(email.subject.as_str());
+
+        for m in self.parse_mailboxes(&email.to)? {
+            builder = builder.to(m);
+        }
+        for m in self.parse_mailboxes(&email.cc)? {
+            builder = builder.cc(m);
+        }
+        for m in self.parse_mailboxes(&email.bcc)? {
+            builder = builder.bcc(m);
+        }
+        for m in self.parse_mailboxes(&email.reply_to)? {
+            builder = builder.reply_to(m);
+        }
+
+        Ok(builder)
+    }
+
+    /// Lays out the text and HTML bodies; `inline` holds the parts of
+    /// [`Email::inline_images`].
+    fn body(&self, email: &Email, inline: Vec<SinglePart>) -> Body {
+        let text = match (&email.text, &email.html) {
+            (Some(text), _) => Some(text.clone()),
+            (None, Some(html)) if self.auto_text || email.text_from_html => {
+                Some(crate::html_text::html_to_text_width(html, self.text_width))
+            }
+            _ => None,
+        };
+        let Some(html) = &email.html else {
+            return Body::Single(SinglePart::plain(text.unwrap_or_default()));
+        };
+        let html = SinglePart::html(html.clone());
+        let html = if inline.is_empty() {
+            Body::Single(html)
+        } else {
+            let related = MultiPart::related().singlepart(html);
+            Body::Multi(inline.into_iter().fold(related, |mp, part| mp.singlepart(part)))
+        };
+        match (text, html) {
+            (None, html) => html,
+            (Some(text), Body::Single(html)) => {
+                Body::Multi(MultiPart::alternative().singlepart(SinglePart::plain(text)).singlepart(html))
+            }
+            (Some(text), Body::Multi(related)) => {
+                Body::Multi(MultiPart::alternative().singlepart(SinglePart::plain(text)).multipart(related))
+            }
+        }
+    }
+
+    fn apply_headers(email: &Email, msg: &mut Message) -> Result<(), MailkitError> {
+        for (name, value) in &email.headers {
+            let name = HeaderName::new_from_ascii(name.clone())
+                .map_err(|_| MailkitError::Validation(format!("Invalid header name: {}", name)))?;
+            msg.headers_mut().insert_raw(HeaderValue::new(name, value.clone()));
+        }
+        Ok(())
+    }
+
+    /// Builds the MIME message for `email`, reading attachments from disk.
+    pub fn build_message(&self, email: &Email) -> Result<Message, MailkitError> {
+        let builder = self.create_base_message(email)?;
+        let inline = email
+            .inline_images
+            .iter()
+            .map(InlineImage::part)
+            .collect::<Result<Vec<_>, _>>()?;
+        let content = self.body(email, inline);
+
+        let mut msg = if email.attachments.is_empty() {
+            content.into_message(builder)?
+        } else {
+            let multipart = content.into_mixed();
+            let multipart = self.attach(multipart, &email.attachments)?;
+            builder.multipart(multipart)?
+        };
+        Self::apply_headers(email, &mut msg)?;
+        self.sign(&mut msg);
+        Ok(msg)
+    }
+
+    /// Async version of [`EmailSender::build_message`] reading attachments
+    /// with `tokio::fs`.
+    pub async fn build_message_async(&self, email: &Email) -> Result<Message, MailkitError> {
+        let builder = self.create_base_message(email)?;
+        let mut inline = Vec::with_capacity(email.inline_images.len());
+        for image in &email.inline_images {
+            inline.push(image.part_async().await?);
+        }
+        let content = self.body(email, inline);
+
+        let mut msg = if email.attachments.is_empty() {
+            content.into_message(builder)?
+        } else {
+            let multipart = content.into_mixed();
+            let multipart = self.attach_async(multipart, &email.attachments).await?;
+            builder.multipart(multipart)?
+        };
+        Self::apply_headers(email, &mut msg)?;
+        self.sign(&mut msg);
+        Ok(msg)
+    }
+
+    /// Adds the `DKIM-Signature` header if DKIM is configured. Must run
+    /// after every other header is set.
+    fn sign(&self, msg: &mut Message) {
+        if let Some(dkim) = &self.dkim {
+            msg.sign(dkim);
+        }
+    }
+
+    pub fn attach_files(
+        &self,
+        multipart: MultiPart,
+        attachments: &[String],
+    ) -> Result<MultiPart, MailkitError> {
+        let sources: Vec<_> = attachments.iter().map(AttachmentSource::path).collect();
+        self.attach(multipart, &sources)
+    }
+
+    pub async fn attach_files_async(
+        &self,
+        multipart: MultiPart,
+        attachments: &[String],
+    ) -> Result<MultiPart, MailkitError> {
+        let sources: Vec<_> = attachments.iter().map(AttachmentSource::path).collect();
+        self.attach_async(multipart, &sources).await
+    }
+
+    fn attach(&self, multipart: MultiPart, attachments: &[AttachmentSource]) -> Result<MultiPart, MailkitError> {
+        let mut mp = multipart;
+        for attachment in attachments {
+            mp = mp.singlepart(attachment.part(self.mime_policy)?);
+        }
+        Ok(mp)
+    }
+
+    async fn attach_async(
+        &self,
+        multipart: MultiPart,
+        attachments: &[AttachmentSource],
+    ) -> Result<MultiPart, MailkitError> {
+        let mut mp = multipart;
+        for attachment in attachments {
+            mp = mp.singlepart(attachment.part_async(self.mime_policy).await?);
+        }
+        Ok(mp)
+    }
+
+    /// Closes connections held by the transport, e.g. idle pooled SMTP
+    /// connections. Later sends open new ones.
+    pub fn close(&self) {
+        self.transport.close();
+    }
 
-impl From<std::io::Error> for MailkitError {
-    fn from(e: std::io::Error) -> Self {
-        MailkitError::Io(e)
+    /// Async version of [`EmailSender::close`].
+    pub async fn close_async(&self) {
+        self.async_transport.close_async().await;
     }
-}
 
-impl From<lettre::transport::smtp::Error> for MailkitError {
-    fn from(e: lettre::transport::smtp::Error) -> Self {
-        MailkitError::Smtp(e)
+    /// Sends `email` through the configured transport.
+    ///
+    /// Blocks while waiting for a [`max_in_flight`](EmailSenderBuilder::max_in_flight)
+    /// slot or a [`rate_limit`](EmailSenderBuilder::rate_limit) token. Inside
+    /// a tokio runtime prefer [`deliver_async`](EmailSender::deliver_async).
+    pub fn deliver(&self, email: &Email) -> Result<Delivery, MailkitError> {
+        self.deliver_with(email, false)
     }
-}
 
-impl From<tera::Error> for MailkitError {
-    fn from(e: tera::Error) -> Self {
-        MailkitError::Tera(e)
+    /// Sends `email` through the configured transport without blocking the
+    /// runtime.
+    pub async fn deliver_async(&self, email: &Email) -> Result<Delivery, MailkitError> {
+        self.deliver_async_with(email, false).await
     }
-}
 
-impl From<lettre::error::Error> for MailkitError {
-    fn from(e: lettre::error::Error) -> Self {
-        MailkitError::Build(e)
+    /// The outbox set with [`EmailSenderBuilder::outbox`].
+    pub fn outbox(&self) -> Option<&Outbox> {
+        self.outbox.as_ref()
     }
-}
 
-impl From<lettre::address::AddressError> for MailkitError {
-    fn from(e: lettre::address::AddressError) -> Self {
-        MailkitError::Address(e)
+    /// Builds `email` and stores it in the outbox instead of sending it,
+    /// returning the outbox entry id.
+    ///
+    /// Once this returns the message is on disk and will be delivered by a
+    /// worker from [`EmailSender::spawn_outbox_worker`] or by
+    /// [`EmailSender::process_outbox`], even if the process restarts in
+    /// between. Fails if no outbox is configured.
+    pub fn enqueue(&self, email: &Email) -> Result<String, MailkitError> {
+        self.enqueue_at(email, SystemTime::now())
     }
-}
 
-/// Main entry point for sending emails.
-pub struct EmailSender {
-    user_email: String,
-    user_password: String,
-    smtp_server: String,
-    port: u16,
-    timeout: Duration,
-    validate_emails: bool,
-    tera: Tera,
-}
+    /// Like [`EmailSender::enqueue`], but the message is not sent before
+    /// `at`. Use [`Outbox::cancel`] or [`Outbox::reschedule`] with the
+    /// returned id to change plans.
+    pub fn enqueue_at(&self, email: &Email, at: SystemTime) -> Result<String, MailkitError> {
+        let outbox = self
+            .outbox
+            .as_ref()
+            .ok_or_else(|| MailkitError::Validation("no outbox configured".into()))?;
+        let key = self.claim_key(email)?;
+        let pushed = self.build_message(email).and_then(|msg| {
+            let message_id = msg.headers().get_raw("Message-ID").unwrap_or_default().to_string();
+            outbox.push(msg.envelope(), &message_id, &msg.formatted(), at).map(|id| (message_id, id))
+        });
+        self.settle_key(key, &pushed);
+        let (message_id, id) = pushed?;
+        crate::info!("Queued {} for {} as {}", message_id, email.to.join(", "), id);
+        Ok(id)
+    }
 
-impl EmailSender {
-    pub fn new<U: Into<String>>(
-        user_email: U,
-        smtp_server: U,
-        user_password: U,
-        port: u16,
-        timeout_secs: u64,
-        validate_emails: bool,
-    ) -> Result<Self, MailkitError> {
-        let email_str = user_email.into();
-        let user_email = if validate_emails {
-            Self::check_email(&email_str)?
-        } else {
-            email_str.clone()
+    /// Like [`EmailSender::enqueue`], but the message is not sent before
+    /// `delay` has passed.
+    pub fn enqueue_after(&self, email: &Email, delay: Duration) -> Result<String, MailkitError> {
+        self.enqueue_at(email, SystemTime::now() + delay)
+    }
+
+    /// Makes one attempt at every outbox message that is due and returns
+    /// how many were attempted. Failures are rescheduled or dead-lettered
+    /// according to the outbox's retry policy.
+    pub async fn process_outbox(&self) -> Result<usize, MailkitError> {
+        self.outbox_pass().await.map(|(attempted, _)| attempted)
+    }
+
+    /// Starts a Tokio task that delivers outbox messages until
+    /// [`OutboxWorker::shutdown`] is called. It wakes up when a message is
+    /// enqueued, when a retry is due and otherwise every
+    /// [`poll_interval`](Outbox::poll_interval).
+    ///
+    /// Must be called from within a Tokio runtime. Fails if no outbox is
+    /// configured.
+    pub fn spawn_outbox_worker(self: &Arc<Self>) -> Result<OutboxWorker, MailkitError> {
+        let Some(outbox) = self.outbox.clone() else {
+            return Err(MailkitError::Validation("no outbox configured".into()));
         };
+        let sender = Arc::clone(self);
+        let cancel = CancelToken::new();
+        let stop = cancel.clone();
+        let handle = tokio::spawn(async move {
+            while !stop.is_cancelled() {
+                let wait = match sender.outbox_pass().await {
+                    Ok((_, next)) => next.map_or(outbox.poll(), |n| n.min(outbox.poll())),
+                    Err(err) => {
+                        crate::error!("Outbox pass failed: {}", err);
+                        outbox.poll()
+                    }
+                };
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    _ = outbox.woken() => {}
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+        });
+        Ok(OutboxWorker { cancel, handle })
+    }
 
-        let template_dir = PathBuf::from(
-            env::var("MAILKIT_TEMPLATE_DIR").unwrap_or_else(|_| "templates".to_owned()),
-        )
-            .join("**/*");
-        let tera = Tera::new(&template_dir.to_string_lossy())?;
-
-        crate::info!("EmailSender initialized for {}", user_email);
-
-        Ok(Self {
-            user_email,
-            user_password: user_password.into(),
-            smtp_server: smtp_server.into(),
-            port,
-            timeout: Duration::from_secs(timeout_secs),
-            validate_emails,
-            tera,
-        })
+    /// Sends every due outbox message once. Returns the number attempted
+    /// and the time until the next scheduled retry.
+    async fn outbox_pass(&self) -> Result<(usize, Option<Duration>), MailkitError> {
+        use futures::stream::{self, StreamExt};
+
+        let Some(outbox) = &self.outbox else {
+            return Ok((0, None));
+        };
+        let (due, next) = outbox.due()?;
+        let concurrency = self.limiter.concurrency();
+        let results: Vec<Result<bool, MailkitError>> = stream::iter(due)
+            .map(|id| async move {
+                let Some(claimed) = outbox.claim(&id)? else {
+                    return Ok(false);
+                };
+                let permit = self.limiter.acquire_async().await;
+                let result = self
+                    .async_transport
+                    .send_raw_async(claimed.envelope(), claimed.raw())
+                    .await;
+                drop(permit);
+                outbox.finish(claimed, result)?;
+                Ok(true)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+        let mut attempted = 0;
+        for result in results {
+            if result? {
+                attempted += 1;
+            }
+        }
+        let (_, next_retry) = outbox.due()?;
+        Ok((attempted, next_retry.or(next)))
     }
 
-    pub fn from_env() -> Result<Self, MailkitError> {
-        let user_email = env::var("EMAIL").map_err(|_| MailkitError::MissingEnvVar("EMAIL"))?;
-        let server = env::var("SMTP_SERVER").map_err(|_| MailkitError::MissingEnvVar("SMTP_SERVER"))?;
-        let password = env::var("EMAIL_PASSWORD").map_err(|_| MailkitError::MissingEnvVar("EMAIL_PASSWORD"))?;
-        let port_str = env::var("SMTP_PORT").unwrap_or_else(|_| "587".into());
-        let port = port_str
-            .parse()
-            .map_err(|_| MailkitError::Validation("Invalid SMTP_PORT".into()))?;
+    /// Sends a copy of `email` to each of `recipients` separately, replacing
+    /// its `To` addresses. Cc and Bcc are kept on every copy.
+    pub fn deliver_bulk(&self, email: &Email, recipients: &[String], mode: BulkMode) -> BulkReport {
+        let emails = recipients.iter().map(|rcpt| (rcpt.clone(), Ok(email.with_to(rcpt))));
+        self.bulk(emails, false, mode)
+    }
 
-        Self::new(user_email, server, password, port, 10, true)
+    /// Async version of [`EmailSender::deliver_bulk`]; copies are sent
+    /// concurrently, up to
+    /// [`max_in_flight`](EmailSenderBuilder::max_in_flight) (16 if unset) at
+    /// a time.
+    pub async fn deliver_bulk_async(&self, email: &Email, recipients: &[String], mode: BulkMode) -> BulkReport {
+        let emails = recipients.iter().map(|rcpt| (rcpt.clone(), Ok(email.with_to(rcpt))));
+        self.bulk_async(emails, false, mode).await
     }
 
-    fn check_email(addr: &str) -> Result<String, MailkitError> {
-        fn is_valid_email(addr: &str) -> bool {
-            let mut parts = addr.split('@');
-            if let (Some(local), Some(domain), None) = (parts.next(), parts.next(), parts.next()) {
-                !local.is_empty() && !domain.is_empty() && domain.contains('.')
+    /// Sends prepared messages one after another. An `Err` item is a
+    /// recipient whose message could not be prepared and is reported as is.
+    fn bulk<I>(&self, emails: I, force_tls: bool, mode: BulkMode) -> BulkReport
+    where
+        I: IntoIterator<Item = (String, Result<Email, Outcome>)>,
+    {
+        let start = Instant::now();
+        let mut results = Vec::new();
+        let mut stopped = false;
+        for (recipient, email) in emails {
+            let outcome = if stopped {
+                Outcome::Skipped
             } else {
-                false
-            }
+                crate::info!("Bulk sending to {}", recipient);
+                let outcome = match email {
+                    Ok(email) => Outcome::from(self.deliver_with(&email, force_tls)),
+                    Err(outcome) => outcome,
+                };
+                if let Some(err) = outcome.error() {
+                    crate::error!("Bulk send to {} failed: {}", recipient, err);
+                    stopped = mode == BulkMode::StopOnFirstError;
+                }
+                outcome
+            };
+            results.push(RecipientResult { recipient, outcome });
         }
-
-        if is_valid_email(addr) {
-            Ok(addr.to_lowercase())
-        } else {
-            crate::error!("Invalid email address: {}", addr);
-            Err(MailkitError::Validation(addr.into()))
+        BulkReport {
+            results,
+            elapsed: start.elapsed(),
         }
     }
 
-    fn create_base_message<I, S>(
-        &self,
-        subject: &str,
-        to: I,
-        cc: Option<I>,
-        bcc: Option<I>,
-    ) -> Result<lettre::message::MessageBuilder, MailkitError>
+    async fn bulk_async<I>(&self, emails: I, force_tls: bool, mode: BulkMode) -> BulkReport
     where
-        I: IntoIterator<Item = S>,
-        S: Into<String>,
+        I: IntoIterator<Item = (String, Result<Email, Outcome>)>,
     {
-        let mut builder = Message::builder()
-            .from(self.user_email.parse::<Mailbox>()?)
-            .subject(subject);
+        use futures::stream::{self, StreamExt};
+        use std::sync::atomic::{AtomicBool, Ordering};
 
-        let to_addrs: Vec<Mailbox> = to
-            .into_iter()
-            .map(|rcpt| {
-                let s = rcpt.into();
-                if self.validate_emails {
-                    Self::check_email(&s)
-                } else {
-                    Ok(s)
+        let start = Instant::now();
+        let stopped = AtomicBool::new(false);
+        let mut results = Vec::new();
+        let mut sends = Vec::new();
+        for (i, (recipient, email)) in emails.into_iter().enumerate() {
+            results.push(RecipientResult {
+                recipient: recipient.clone(),
+                outcome: Outcome::Skipped,
+            });
+            let stopped = &stopped;
+            sends.push(async move {
+                if stopped.load(Ordering::SeqCst) {
+                    return (i, Outcome::Skipped);
+                }
+                crate::info!("Bulk async sending to {}", recipient);
+                let outcome = match email {
+                    Ok(email) => Outcome::from(self.deliver_async_with(&email, force_tls).await),
+                    Err(outcome) => outcome,
+                };
+                (i, outcome)
+            });
+        }
+
+        // Only start as many sends as may run at once so stopping early
+        // leaves the rest untouched. Sends already running when one fails
+        // are still awaited, so their real outcome is reported.
+        let mut futures = stream::iter(sends).buffer_unordered(self.limiter.concurrency());
+        while let Some((i, outcome)) = futures.next().await {
+            if let Some(err) = outcome.error() {
+                crate::error!("Bulk send to {} failed: {}", results[i].recipient, err);
+                if mode == BulkMode::StopOnFirstError {
+                    stopped.store(true, Ordering::SeqCst);
                 }
-            })
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .map(|s| s.parse())
-            .collect::<Result<_, _>>()?;
-        for m in &to_addrs {
-            builder = builder.to(m.clone());
-        }
-
-        if let Some(cc_iter) = cc {
-            let cc_addrs: Vec<Mailbox> = cc_iter
-                .into_iter()
-                .map(|c| {
-                    let s = c.into();
-                    if self.validate_emails {
-                        Self::check_email(&s)
-                    } else {
-                        Ok(s)
-                    }
-                })
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .map(|s| s.parse())
-                .collect::<Result<_, _>>()?;
-            for m in &cc_addrs {
-                builder = builder.cc(m.clone());
             }
+            results[i].outcome = outcome;
+        }
+        BulkReport {
+            results,
+            elapsed: start.elapsed(),
         }
+    }
+
+    /// Sends a copy of `email` to every recipient pulled from `recipients`,
+    /// yielding each result as soon as it is known.
+    ///
+    /// Recipients are only pulled while fewer than
+    /// [`max_in_flight`](EmailSenderBuilder::max_in_flight) sends (16 if
+    /// unset) are running, so a slow SMTP server slows down the source
+    /// instead of buffering it. Recipient contexts are ignored. See
+    /// [`CancelToken`] for stopping early.
+    pub fn deliver_stream<'a, S>(
+        &'a self,
+        email: &'a Email,
+        recipients: S,
+        cancel: CancelToken,
+    ) -> impl futures::Stream<Item = RecipientResult> + 'a
+    where
+        S: futures::Stream + 'a,
+        S::Item: Into<Recipient>,
+    {
+        self.stream_with(recipients, cancel, move |rcpt| Ok(email.with_to(rcpt.address())))
+    }
+
+    /// Streaming version of [`EmailSender::send_bulk_template`]; see
+    /// [`EmailSender::deliver_stream`] for backpressure and cancellation.
+    pub fn send_template_stream<'a, S>(
+        &'a self,
+        recipients: S,
+        subject_template: &'a str,
+        template_name: &'a str,
+        base_context: &'a crate::json::JsonValue,
+        cancel: CancelToken,
+    ) -> impl futures::Stream<Item = RecipientResult> + 'a
+    where
+        S: futures::Stream + 'a,
+        S::Item: Into<Recipient>,
+    {
+        let subject = self.merge_subject(subject_template);
+        self.stream_with(recipients, cancel, move |rcpt| {
+            self.render_merge(rcpt.address(), rcpt.context(), &subject, template_name, base_context)
+        })
+    }
+
+    fn stream_with<'a, S, F>(
+        &'a self,
+        recipients: S,
+        cancel: CancelToken,
+        prepare: F,
+    ) -> impl futures::Stream<Item = RecipientResult> + 'a
+    where
+        S: futures::Stream + 'a,
+        S::Item: Into<Recipient>,
+        F: Fn(&Recipient) -> Result<Email, Outcome> + 'a,
+    {
+        use futures::stream::StreamExt;
 
-        if let Some(bcc_iter) = bcc {
-            let bcc_addrs: Vec<Mailbox> = bcc_iter
-                .into_iter()
-                .map(|b| {
-                    let s = b.into();
-                    if self.validate_emails {
-                        Self::check_email(&s)
-                    } else {
-                        Ok(s)
+        let concurrency = self.limiter.concurrency();
+        recipients
+            .take_until(async move { cancel.cancelled().await })
+            .map(move |rcpt| {
+                let rcpt: Recipient = rcpt.into();
+                let email = prepare(&rcpt);
+                async move {
+                    crate::info!("Stream sending to {}", rcpt.address());
+                    let outcome = match email {
+                        Ok(email) => Outcome::from(self.deliver_async_with(&email, false).await),
+                        Err(outcome) => outcome,
+                    };
+                    if let Some(err) = outcome.error() {
+                        crate::error!("Stream send to {} failed: {}", rcpt.address(), err);
                     }
-                })
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .map(|s| s.parse())
-                .collect::<Result<_, _>>()?;
-            for m in &bcc_addrs {
-                builder = builder.bcc(m.clone());
-            }
+                    RecipientResult {
+                        recipient: rcpt.address().to_string(),
+                        outcome,
+                    }
+                }
+            })
+            .buffer_unordered(concurrency)
+    }
+
+    /// `force_tls` is the legacy `use_tls` flag: when the transport is SMTP
+    /// it forces [`TlsMode::Wrapper`] for this message.
+    fn deliver_with(&self, email: &Email, force_tls: bool) -> Result<Delivery, MailkitError> {
+        let key = self.claim_key(email)?;
+        let result = self.send_with(email, force_tls);
+        self.settle_key(key, &result);
+        result
+    }
+
+    async fn deliver_async_with(&self, email: &Email, force_tls: bool) -> Result<Delivery, MailkitError> {
+        let key = self.claim_key(email)?;
+        let result = self.send_async_with(email, force_tls).await;
+        self.settle_key(key, &result);
+        result
+    }
+
+    /// Records the idempotency key of `email`, failing with
+    /// [`MailkitError::Duplicate`] if it was already sent. Returns the key to
+    /// pass to [`EmailSender::settle_key`].
+    fn claim_key(&self, email: &Email) -> Result<Option<String>, MailkitError> {
+        let Some(key) = email.idempotency_key() else {
+            return Ok(None);
+        };
+        if !self.idempotency_store.insert(key, self.idempotency_window)? {
+            crate::warn!("Skipping duplicate message with idempotency key {}", key);
+            return Err(MailkitError::Duplicate(key.to_string()));
         }
+        Ok(Some(key.to_string()))
+    }
 
-        Ok(builder)
+    /// Forgets a claimed key again if the send failed, so it can be retried.
+    fn settle_key<T>(&self, key: Option<String>, result: &Result<T, MailkitError>) {
+        if let (Some(key), Err(_)) = (key, result)
+            && let Err(err) = self.idempotency_store.remove(&key)
+        {
+            crate::error!("Failed to release idempotency key {}: {}", key, err);
+        }
     }
 
-    pub fn attach_files(
-        &self,
-        multipart: MultiPart,
-        attachments: &[String],
-    ) -> Result<MultiPart, MailkitError> {
-        let mut mp = multipart;
-        for path in attachments {
-            let data = fs::read(path)?;
-            let filename = Path::new(path)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("attachment");
-            let mime = mime_guess::from_path(path).first_or_octet_stream();
-            let ctype = lettre::message::header::ContentType::parse(mime.essence_str())
-                .map_err(|_| MailkitError::Validation("Invalid content type".into()))?;
-            let attachment = Attachment::new(filename.to_string())
-                .body(data, ctype);
-            mp = mp.singlepart(attachment);
+    fn send_with(&self, email: &Email, force_tls: bool) -> Result<Delivery, MailkitError> {
+        crate::info!("Sending email to: {}", email.to.join(", "));
+        let start = Instant::now();
+        let msg = self.build_message(email)?;
+        let raw = msg.formatted();
+
+        let mut attempt = 1;
+        loop {
+            let permit = self.limiter.acquire();
+            let result = match &self.smtp {
+                Some(smtp) if force_tls => smtp.send_with(TlsMode::Wrapper, msg.envelope(), &raw),
+                _ => self.transport.send_raw(msg.envelope(), &raw),
+            };
+            drop(permit);
+            match result {
+                Ok(response) => return Ok(Self::delivery(&msg, response, start)),
+                Err(err) if self.retry.should_retry(&err, attempt) => {
+                    let delay = self.retry.delay(attempt);
+                    crate::warn!("Attempt {} failed: {}; retrying in {:?}", attempt, err, delay);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
         }
-        Ok(mp)
     }
 
-    pub async fn attach_files_async(
-        &self,
-        multipart: MultiPart,
-        attachments: &[String],
-    ) -> Result<MultiPart, MailkitError> {
-        let mut mp = multipart;
-        for path in attachments {
-            let data = tokio_fs::read(path).await?;
-            let filename = Path::new(path)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("attachment");
-            let mime = mime_guess::from_path(path).first_or_octet_stream();
-            let ctype = lettre::message::header::ContentType::parse(mime.essence_str())
-                .map_err(|_| MailkitError::Validation("Invalid content type".into()))?;
-            let attachment = Attachment::new(filename.to_string())
-                .body(data, ctype);
-            mp = mp.singlepart(attachment);
+    async fn send_async_with(&self, email: &Email, force_tls: bool) -> Result<Delivery, MailkitError> {
+        crate::info!("Async sending to: {}", email.to.join(", "));
+        let start = Instant::now();
+        let msg = self.build_message_async(email).await?;
+        let raw = msg.formatted();
+
+        let mut attempt = 1;
+        loop {
+            let permit = self.limiter.acquire_async().await;
+            let result = match &self.smtp {
+                Some(smtp) if force_tls => smtp.send_with_async(TlsMode::Wrapper, msg.envelope(), &raw).await,
+                _ => self.async_transport.send_raw_async(msg.envelope(), &raw).await,
+            };
+            drop(permit);
+            match result {
+                Ok(response) => return Ok(Self::delivery(&msg, response, start)),
+                Err(err) if self.retry.should_retry(&err, attempt) => {
+                    let delay = self.retry.delay(attempt);
+                    crate::warn!("Attempt {} failed: {}; retrying in {:?}", attempt, err, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
         }
-        Ok(mp)
     }
 
-    fn build_sync_mailer(&self, use_tls: bool) -> Result<SmtpTransport, MailkitError> {
-        let creds = Credentials::new(self.user_email.clone(), self.user_password.clone());
-        let builder = if self.port == 465 || use_tls {
-            SmtpTransport::relay(&self.smtp_server)?
-        } else {
-            SmtpTransport::starttls_relay(&self.smtp_server)?
-        };
-        Ok(builder
-            .credentials(creds)
-            .port(self.port)
-            .timeout(Some(self.timeout))
-            .build())
+    fn delivery(msg: &Message, response: String, start: Instant) -> Delivery {
+        Delivery {
+            message_id: msg.headers().get_raw("Message-ID").unwrap_or_default().to_string(),
+            response,
+            elapsed: start.elapsed(),
+        }
     }
 
-    fn build_async_mailer(&self, use_tls: bool) -> Result<AsyncSmtpTransport<Tokio1Executor>, MailkitError> {
-        let creds = Credentials::new(self.user_email.clone(), self.user_password.clone());
-        let builder = if self.port == 465 || use_tls {
-            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp_server)?
-        } else {
-            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.smtp_server)?
-        };
-        Ok(builder
-            .credentials(creds)
-            .port(self.port)
-            .timeout(Some(self.timeout))
-            .build())
+    /// Builds an [`Email`] from the positional arguments of the legacy
+    /// `send*` methods.
+    #[allow(clippy::too_many_arguments)]
+    fn legacy_email<I, S>(
+        recipients: I,
+        subject: &str,
+        body: &str,
+        cc: Option<I>,
+        bcc: Option<I>,
+        attachments: Option<&[String]>,
+        html: bool,
+    ) -> Result<Email, MailkitError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut builder = Email::builder().subject(subject);
+        builder = if html { builder.html(body) } else { builder.text(body) };
+        for rcpt in recipients {
+            builder = builder.to(rcpt);
+        }
+        for addr in cc.into_iter().flatten() {
+            builder = builder.cc(addr);
+        }
+        for addr in bcc.into_iter().flatten() {
+            builder = builder.bcc(addr);
+        }
+        for path in attachments.unwrap_or_default() {
+            builder = builder.attachment(path.clone());
+        }
+        builder.build()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn send<I, S>(
         &self,
         recipients: I,
@@ -327,30 +1780,14 @@ impl EmailSender {
         I: IntoIterator<Item = S> + Clone,
         S: Into<String> + Clone,
     {
-        let recipients_vec: Vec<String> = recipients.clone().into_iter().map(|x| x.into()).collect();
-        crate::info!("Sending email to: {}", recipients_vec.join(", "));
-
-        let builder = self.create_base_message(subject, recipients.clone(), cc.clone(), bcc.clone())?;
-
-        let content = if html {
-            SinglePart::html(body.to_string())
-        } else {
-            SinglePart::plain(body.to_string())
-        };
-
-        let msg = if let Some(files) = attachments {
-            let multipart = MultiPart::mixed().singlepart(content);
-            let multipart = self.attach_files(multipart, files)?;
-            builder.multipart(multipart)?
-        } else {
-            builder.singlepart(content)?
-        };
-        let mailer = self.build_sync_mailer(use_tls)?;
-
-        mailer.send(&msg)?;
-        Ok(())
+        let email = Self::legacy_email(recipients, subject, body, cc, bcc, attachments, html)?;
+        self.deliver_with(&email, use_tls).map(|_| ())
     }
 
+    /// Sends the same message to each recipient separately. Every
+    /// recipient is attempted; see the returned [`BulkReport`] for who
+    /// failed. Use [`EmailSender::deliver_bulk`] to stop at the first error.
+    #[allow(clippy::too_many_arguments)]
     pub fn send_bulk(
         &self,
         recipients: Vec<String>,
@@ -361,25 +1798,17 @@ impl EmailSender {
         attachments: Option<&[String]>,
         use_tls: bool,
         html: bool,
-    ) -> Result<(), MailkitError> {
-        for rcpt in &recipients {
-            crate::info!("Bulk sending to {}", rcpt);
-
-            self.send(
-                vec![rcpt.clone()],
-                subject,
-                body,
-                cc.clone(),
-                bcc.clone(),
-                attachments,
-                use_tls,
-                html,
-            )?;
-        }
-
-        Ok(())
+    ) -> BulkReport {
+        let emails = recipients.into_iter().map(|rcpt| {
+            let email = Self::legacy_email(vec![rcpt.clone()], subject, body, cc.clone(), bcc.clone(), attachments, html);
+            (rcpt, email.map_err(Outcome::Failed))
+        });
+        self.bulk(emails, use_tls, BulkMode::ContinueOnError)
     }
 
+    /// Async version of [`EmailSender::send_bulk`]; messages are sent
+    /// concurrently, see [`EmailSender::deliver_bulk_async`].
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_bulk_async(
         &self,
         recipients: Vec<String>,
@@ -390,32 +1819,15 @@ impl EmailSender {
         attachments: Option<&[String]>,
         use_tls: bool,
         html: bool,
-    ) -> Result<(), MailkitError> {
-        use futures::stream::{FuturesUnordered, StreamExt};
-
-        let mut futures = FuturesUnordered::new();
-        for rcpt in recipients {
-            crate::info!("Bulk async sending to {}", rcpt);
-            futures.push(self.send_async(
-                vec![rcpt],
-                subject,
-                body,
-                cc.clone(),
-                bcc.clone(),
-                attachments,
-                use_tls,
-                html,
-            ));
-        }
-
-        while let Some(res) = futures.next().await {
-            res?;
-        }
-
-        Ok(())
+    ) -> BulkReport {
+        let emails = recipients.into_iter().map(|rcpt| {
+            let email = Self::legacy_email(vec![rcpt.clone()], subject, body, cc.clone(), bcc.clone(), attachments, html);
+            (rcpt, email.map_err(Outcome::Failed))
+        });
+        self.bulk_async(emails, use_tls, BulkMode::ContinueOnError).await
     }
 
-
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_async<I, S>(
         &self,
         recipients: I,
@@ -431,31 +1843,230 @@ impl EmailSender {
         I: IntoIterator<Item = S> + Clone + Send + 'static,
         S: Into<String> + Clone + Send + 'static,
     {
-        let recipients_vec: Vec<String> = recipients.clone().into_iter().map(|x| x.into()).collect();
-        crate::info!("Async sending to: {}", recipients_vec.join(", "));
+        let email = Self::legacy_email(recipients, subject, body, cc, bcc, attachments, html)?;
+        self.deliver_async_with(&email, use_tls).await.map(|_| ())
+    }
+
+    /// Renders `template_name` with `context` into the HTML body of a new
+    /// [`Email`] addressed to `recipient`.
+    ///
+    /// For an `.html` (or `.htm`) template, companions with the same stem
+    /// are rendered with the same context when they exist: `welcome.txt`
+    /// becomes the text body and `welcome.subject` the subject. The subject
+    /// can also come from front matter at the top of the HTML template,
+    /// which takes precedence over a `.subject` file:
+    ///
+    /// ```text
+    /// ---
+    /// subject: Welcome, {{ name }}!
+    /// ---
+    /// <h1>Hi {{ name }}</h1>
+    /// ```
+    ///
+    /// `subject` is only used when neither is present.
+    pub fn render_template<S: Into<String>>(
+        &self,
+        recipient: S,
+        subject: &str,
+        template_name: &str,
+        context: &crate::json::JsonValue,
+    ) -> Result<EmailBuilder, MailkitError> {
+        let ctx = Self::template_context(&[context]);
+        self.render_with(recipient, template_name, &ctx, || Ok(subject.to_string()))
+    }
 
-        let builder = self.create_base_message(subject, recipients.clone(), cc.clone(), bcc.clone())?;
+    /// Renders `template_name` with its companions and front matter, see
+    /// [`EmailSender::render_template`]. `subject` is only called when
+    /// neither provides a subject.
+    fn render_with<S: Into<String>>(
+        &self,
+        recipient: S,
+        template_name: &str,
+        ctx: &Context,
+        subject: impl FnOnce() -> Result<String, MailkitError>,
+    ) -> Result<EmailBuilder, MailkitError> {
+        let (rendered, images) = self.render_html(template_name, ctx)?;
+        let (front_subject, body) = split_front_matter(&rendered)
+            .map_err(|e| MailkitError::Validation(format!("{}: {}", template_name, e)))?;
 
-        let content = if html {
-            SinglePart::html(body.to_string())
-        } else {
-            SinglePart::plain(body.to_string())
+        let mut builder = Email::builder().to(recipient).html(body);
+        builder.email.inline_images.extend(images);
+        let stem = template_name
+            .strip_suffix(".html")
+            .or_else(|| template_name.strip_suffix(".htm"));
+        let mut file_subject = None;
+        if let Some(stem) = stem {
+            if let Some(text) = self.render_companion(&format!("{}.txt", stem), ctx)? {
+                builder = builder.text(text);
+            }
+            file_subject = self
+                .render_companion(&format!("{}.subject", stem), ctx)?
+                .map(|s| one_line(&s));
+        }
+        let subject = match front_subject.or(file_subject) {
+            Some(subject) => subject,
+            None => subject()?,
         };
+        Ok(builder.subject(subject))
+    }
 
-        let msg = if let Some(files) = attachments {
-            let multipart = MultiPart::mixed().singlepart(content);
-            let multipart = self.attach_files_async(multipart, files).await?;
-            builder.multipart(multipart)?
-        } else {
-            builder.singlepart(content)?
-        };
+    /// Renders an HTML template along with the images it embedded through
+    /// the `inline_image` function.
+    fn render_html(&self, name: &str, ctx: &Context) -> Result<(String, Vec<InlineImage>), tera::Error> {
+        let (html, images) = crate::inline::collect(|| self.tera.render(name, ctx));
+        Ok((html?, images))
+    }
+
+    /// Renders `name` if the Tera instance has it.
+    fn render_companion(&self, name: &str, ctx: &Context) -> Result<Option<String>, MailkitError> {
+        if self.tera.get_template(name).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(self.tera.render(name, ctx)?))
+    }
 
-        let mailer = self.build_async_mailer(use_tls)?;
+    /// Like [`EmailSender::render_template`], but the text body is rendered
+    /// from `text_template` instead of being generated from the HTML.
+    pub fn render_template_with_text<S: Into<String>>(
+        &self,
+        recipient: S,
+        subject: &str,
+        html_template: &str,
+        text_template: &str,
+        context: &crate::json::JsonValue,
+    ) -> Result<EmailBuilder, MailkitError> {
+        let ctx = Self::template_context(&[context]);
+        let text = self.tera.render(text_template, &ctx)?;
+        Ok(self.render_template(recipient, subject, html_template, context)?.text(text))
+    }
 
-        mailer.send(msg).await?;
-        Ok(())
+    /// Merges the top-level keys of `layers`; later layers win.
+    fn template_context(layers: &[&crate::json::JsonValue]) -> Context {
+        let mut ctx = Context::new();
+        for layer in layers {
+            if let Some(map) = layer.as_object() {
+                for (k, v) in map {
+                    ctx.insert(k, v);
+                }
+            }
+        }
+        ctx
+    }
+
+    /// Compiles the subject template of a mail merge once, with the
+    /// sender's filters and functions.
+    fn merge_subject(&self, subject_template: &str) -> MergeSubject {
+        let mut tera = self.tera.clone();
+        MergeSubject(match tera.add_raw_template(MERGE_SUBJECT, subject_template) {
+            Ok(()) => Ok(tera),
+            Err(err) => {
+                // tera::Error is not Clone; keep the whole chain for every recipient
+                let mut msg = err.to_string();
+                let mut source = std::error::Error::source(&err);
+                while let Some(err) = source {
+                    msg = format!("{}: {}", msg, err);
+                    source = err.source();
+                }
+                Err(msg)
+            }
+        })
+    }
+
+    /// Renders the subject and body of one mail merge recipient.
+    fn render_merge(
+        &self,
+        recipient: &str,
+        context: &crate::json::JsonValue,
+        subject: &MergeSubject,
+        template_name: &str,
+        base_context: &crate::json::JsonValue,
+    ) -> Result<Email, Outcome> {
+        let ctx = Self::template_context(&[base_context, context]);
+        let rendered = self.render_with(recipient, template_name, &ctx, || match &subject.0 {
+            Ok(tera) => Ok(one_line(&tera.render(MERGE_SUBJECT, &ctx)?)),
+            Err(msg) => Err(tera::Error::msg(msg).into()),
+        });
+        match rendered {
+            Ok(builder) => builder.build().map_err(Outcome::Failed),
+            Err(err) => {
+                crate::error!("Rendering {} for {} failed: {}", template_name, recipient, err);
+                Err(Outcome::RenderFailed(err))
+            }
+        }
+    }
+
+    /// Mail merge: renders `template_name` and the Tera string
+    /// `subject_template` for each recipient with its own context merged
+    /// over `base_context`, then sends the results one by one.
+    ///
+    /// Front matter and companion `.txt` and `.subject` files are used as
+    /// in [`EmailSender::render_template`]; `subject_template` is the
+    /// subject when neither provides one.
+    ///
+    /// Recipients whose template fails to render are reported as
+    /// [`Outcome::RenderFailed`](crate::bulk::Outcome::RenderFailed)
+    /// instead of [`Outcome::Failed`](crate::bulk::Outcome::Failed).
+    ///
+    /// ```no_run
+    /// # use mailkit::{BulkMode, EmailSender, json};
+    /// # fn run(sender: &EmailSender) {
+    /// let recipients = vec![
+    ///     ("ada@example.com".to_string(), json!({"name": "Ada"})),
+    ///     ("alan@example.com".to_string(), json!({"name": "Alan"})),
+    /// ];
+    /// let report = sender.send_bulk_template(
+    ///     recipients,
+    ///     "Hi {{ name }}, your {{ product }} invoice",
+    ///     "invoice.html",
+    ///     &json!({"product": "MailKit"}),
+    ///     BulkMode::ContinueOnError,
+    /// );
+    /// # }
+    /// ```
+    pub fn send_bulk_template<I>(
+        &self,
+        recipients: I,
+        subject_template: &str,
+        template_name: &str,
+        base_context: &crate::json::JsonValue,
+        mode: BulkMode,
+    ) -> BulkReport
+    where
+        I: IntoIterator<Item = (String, crate::json::JsonValue)>,
+    {
+        let subject = self.merge_subject(subject_template);
+        let emails = recipients.into_iter().map(|(rcpt, context)| {
+            let email = self.render_merge(&rcpt, &context, &subject, template_name, base_context);
+            (rcpt, email)
+        });
+        self.bulk(emails, false, mode)
+    }
+
+    /// Async version of [`EmailSender::send_bulk_template`]. Messages are
+    /// rendered up front and sent concurrently.
+    pub async fn send_bulk_template_async<I>(
+        &self,
+        recipients: I,
+        subject_template: &str,
+        template_name: &str,
+        base_context: &crate::json::JsonValue,
+        mode: BulkMode,
+    ) -> BulkReport
+    where
+        I: IntoIterator<Item = (String, crate::json::JsonValue)>,
+    {
+        let subject = self.merge_subject(subject_template);
+        let emails: Vec<_> = recipients
+            .into_iter()
+            .map(|(rcpt, context)| {
+                let email = self.render_merge(&rcpt, &context, &subject, template_name, base_context);
+                (rcpt, email)
+            })
+            .collect();
+        self.bulk_async(emails, false, mode).await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn send_template<S, CI, BI, CCItem, BItem>(
         &self,
         recipient: S,
@@ -480,31 +2091,18 @@ impl EmailSender {
         recipient_str,
         template_name
     );
-        let mut ctx = Context::new();
-        if let Some(map) = context.as_object() {
-            for (k, v) in map {
-                ctx.insert(k, v);
-            }
+        let mut builder = self.render_template(recipient_str, subject, template_name, context)?;
+        for addr in cc.into_iter().flatten() {
+            builder = builder.cc(addr);
+        }
+        for addr in bcc.into_iter().flatten() {
+            builder = builder.bcc(addr);
+        }
+        for path in attachments.unwrap_or_default() {
+            builder = builder.attachment(path.clone());
         }
-        let body = self.tera.render(template_name, &ctx)?;
-
-        let cc_vec = cc
-            .clone()
-            .map(|c| c.into_iter().map(|s| s.into()).collect::<Vec<String>>());
-        let bcc_vec = bcc
-            .clone()
-            .map(|c| c.into_iter().map(|s| s.into()).collect::<Vec<String>>());
 
-        self.send(
-            vec![recipient_str],
-            subject,
-            &body,
-            cc_vec,
-            bcc_vec,
-            attachments,
-            use_tls,
-            true,
-        )
+        self.deliver_with(&builder.build()?, use_tls).map(|_| ())
     }
 
 }
@@ -513,6 +2111,7 @@ impl EmailSender {
 #[deprecated(note = "Use EmailSender directly")]
 pub struct SendAgent(pub EmailSender);
 
+#[allow(deprecated, clippy::too_many_arguments)]
 impl SendAgent {
     #[deprecated(note = "Use EmailSender directly")]
     pub fn send_mail(
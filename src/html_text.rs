@@ -0,0 +1,443 @@
+//! Plain-text alternatives derived from HTML bodies.
+//!
+//! [`EmailSender`](crate::EmailSender) uses [`html_to_text_width`] to add a
+//! text part to every HTML message that has none, unless disabled with
+//! [`EmailSenderBuilder::auto_text`](crate::EmailSenderBuilder::auto_text).
+
+/// Line width used by [`html_to_text`].
+pub const DEFAULT_WIDTH: usize = 78;
+
+/// Converts an HTML body to readable plain text wrapped at
+/// [`DEFAULT_WIDTH`] columns.
+///
+/// - `h1` and `h2` headings are underlined with `=` and `-`
+/// - list items start with `- ` or their number, nested lists are indented
+/// - links become `text (url)`
+/// - table rows become lines with cells separated by ` | `
+/// - images are replaced by their `alt` text
+/// - `blockquote` lines start with `> ` and `pre` blocks are kept as is
+/// - `script`, `style` and `head` contents are dropped
+pub fn html_to_text(html: &str) -> String {
+    html_to_text_width(html, DEFAULT_WIDTH)
+}
+
+/// Like [`html_to_text`], wrapping at `width` columns. A `width` of `0`
+/// disables wrapping.
+pub fn html_to_text_width(html: &str, width: usize) -> String {
+    let mut renderer = Renderer::new(width);
+    for token in tokenize(html) {
+        match token {
+            Token::Text(text) => renderer.text(&decode_entities(text)),
+            Token::Start(name, tag) => renderer.start(&name, tag),
+            Token::End(name) => renderer.end(&name),
+        }
+    }
+    renderer.finish()
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    /// Lowercased name and the whole tag for reading attributes.
+    Start(String, &'a str),
+    End(String),
+}
+
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            tokens.push(Token::Text(rest));
+            return tokens;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with(['!', '?']) {
+            continue;
+        }
+
+        let name = tag_name(tag);
+        if let Some(closing) = tag.strip_prefix('/') {
+            tokens.push(Token::End(tag_name(closing)));
+        } else if matches!(name.as_str(), "script" | "style" | "head" | "title") {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(pos) => rest[pos..].find('>').map_or("", |e| &rest[pos + e + 1..]),
+                None => "",
+            };
+        } else {
+            tokens.push(Token::Start(name, tag));
+        }
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    tokens
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Value of attribute `name` in a raw tag such as `a href="/x"`.
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag.split_once(char::is_whitespace)?.1;
+    loop {
+        rest = rest.trim_start();
+        let key_end = rest.find(|c: char| c == '=' || c.is_whitespace() || c == '/')?;
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let value = if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (value, remaining) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(q).unwrap_or(body.len());
+                    (&body[..end], body.get(end + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            rest = remaining;
+            value
+        } else {
+            // attribute without a value
+            rest = rest.strip_prefix('/').unwrap_or(rest);
+            ""
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+        if key.is_empty() {
+            return None;
+        }
+    }
+}
+
+/// Builds the text line by line while walking the tokens.
+struct Renderer {
+    width: usize,
+    out: Vec<String>,
+    /// Inline text of the current block.
+    line: String,
+    /// A blank line goes before the next output.
+    blank: bool,
+    quote: usize,
+    /// Open lists with the next number of ordered ones.
+    lists: Vec<Option<usize>>,
+    /// Marker of a list item that has not been written yet.
+    marker: Option<String>,
+    /// Open links with where their text starts in `line`.
+    links: Vec<(Option<String>, usize)>,
+    pre: usize,
+    cell: usize,
+}
+
+impl Renderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width,
+            out: Vec::new(),
+            line: String::new(),
+            blank: false,
+            quote: 0,
+            lists: Vec::new(),
+            marker: None,
+            links: Vec::new(),
+            pre: 0,
+            cell: 0,
+        }
+    }
+
+    /// Appends text, collapsing whitespace like a browser outside `pre`.
+    fn text(&mut self, text: &str) {
+        if self.pre > 0 {
+            self.line.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() && c != '\u{a0}' {
+                if !self.line.is_empty() && !self.line.ends_with(' ') {
+                    self.line.push(' ');
+                }
+            } else {
+                self.line.push(c);
+            }
+        }
+    }
+
+    fn start(&mut self, name: &str, tag: &str) {
+        match name {
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "table" | "hr" => self.block(true),
+            "blockquote" => {
+                self.block(true);
+                self.quote += 1;
+            }
+            "pre" => {
+                self.block(true);
+                self.pre += 1;
+            }
+            "ul" | "ol" => {
+                self.block(self.lists.is_empty());
+                let start = attr(tag, "start").and_then(|s| s.trim().parse().ok()).unwrap_or(1);
+                self.lists.push((name == "ol").then_some(start));
+            }
+            "li" => {
+                self.block(false);
+                self.marker = Some(match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                });
+            }
+            "tr" => {
+                self.block(false);
+                self.cell = 0;
+            }
+            "td" | "th" => {
+                if self.cell > 0 {
+                    let trimmed = self.line.trim_end().len();
+                    self.line.truncate(trimmed);
+                    self.line.push_str(" | ");
+                }
+                self.cell += 1;
+            }
+            "br" => {
+                if self.pre > 0 {
+                    self.line.push('\n');
+                } else if self.line.trim().is_empty() {
+                    self.blank = true;
+                } else {
+                    self.flush();
+                }
+            }
+            "a" => {
+                let start = self.line.len();
+                self.links.push((attr(tag, "href"), start));
+            }
+            "img" => {
+                if let Some(alt) = attr(tag, "alt").filter(|a| !a.trim().is_empty()) {
+                    self.text(" ");
+                    self.text(&alt);
+                    self.text(" ");
+                }
+            }
+            "div" | "section" | "article" | "header" | "footer" | "main" | "nav" | "aside" | "dl" | "dt"
+            | "dd" | "tbody" | "thead" | "tfoot" | "caption" | "center" | "address" | "figure"
+            | "figcaption" => self.block(false),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" => {
+                let underline = if name == "h1" { '=' } else { '-' };
+                let before = self.out.len();
+                self.block(true);
+                let longest = self.out[before..].iter().map(|l| l.chars().count()).max().unwrap_or(0);
+                if longest > 0 {
+                    let prefix = self.quote_prefix();
+                    let len = longest.saturating_sub(prefix.chars().count());
+                    self.out.push(format!("{}{}", prefix, underline.to_string().repeat(len)));
+                }
+            }
+            "p" | "h3" | "h4" | "h5" | "h6" | "table" => self.block(true),
+            "blockquote" => {
+                self.block(true);
+                self.quote = self.quote.saturating_sub(1);
+            }
+            "pre" => {
+                self.block(true);
+                self.pre = self.pre.saturating_sub(1);
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.block(self.lists.is_empty());
+                self.marker = None;
+            }
+            "a" => {
+                if let Some((href, start)) = self.links.pop() {
+                    self.close_link(href, start);
+                }
+            }
+            "li" | "tr" | "div" | "section" | "article" | "header" | "footer" | "main" | "nav" | "aside"
+            | "dl" | "dt" | "dd" | "caption" | "center" | "address" | "figure" | "figcaption" => {
+                self.block(false)
+            }
+            _ => {}
+        }
+    }
+
+    fn close_link(&mut self, href: Option<String>, start: usize) {
+        let Some(href) = href.map(|h| h.trim().to_string()) else {
+            return;
+        };
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            return;
+        }
+        let text = self.line.get(start..).unwrap_or_default().trim().to_string();
+        let shown = href.strip_prefix("mailto:").unwrap_or(&href);
+        if text.is_empty() {
+            self.text(shown);
+        } else if text != href && text != shown {
+            let trimmed = self.line.trim_end().len();
+            self.line.truncate(trimmed);
+            self.line.push_str(&format!(" ({})", shown));
+        }
+    }
+
+    /// Ends the current block; `blank` asks for an empty line after it.
+    fn block(&mut self, blank: bool) {
+        self.flush();
+        if blank {
+            self.blank = true;
+        }
+    }
+
+    fn quote_prefix(&self) -> String {
+        "> ".repeat(self.quote)
+    }
+
+    /// Writes the current line, wrapped and prefixed.
+    fn flush(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        let text = if self.pre > 0 { line.trim_matches('\n') } else { line.trim() };
+        if text.trim().is_empty() {
+            return;
+        }
+        if self.blank && !self.out.is_empty() {
+            self.out.push(self.quote_prefix().trim_end().to_string());
+        }
+        self.blank = false;
+
+        let quote = self.quote_prefix();
+        if self.pre > 0 {
+            for raw in text.lines() {
+                self.out.push(format!("{}{}", quote, raw.trim_end()));
+            }
+            return;
+        }
+        let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+        let (first, rest) = match self.marker.take() {
+            Some(marker) => (
+                format!("{}{}{}", quote, indent, marker),
+                format!("{}{}{}", quote, indent, " ".repeat(marker.chars().count())),
+            ),
+            None if !self.lists.is_empty() => {
+                let prefix = format!("{}{}  ", quote, indent);
+                (prefix.clone(), prefix)
+            }
+            None => (quote.clone(), quote),
+        };
+        wrap(text, self.width, &first, &rest, &mut self.out);
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        let mut text = String::new();
+        for line in &self.out {
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Greedy word wrap. Words longer than `width`, such as URLs, are never
+/// split, and non-breaking spaces keep their words together.
+fn wrap(text: &str, width: usize, first: &str, rest: &str, out: &mut Vec<String>) {
+    let mut current = first.to_string();
+    let mut len = current.chars().count();
+    let mut empty = true;
+    for word in text.split(' ').filter(|w| !w.is_empty()) {
+        let word_len = word.chars().count();
+        if !empty && width > 0 && len + 1 + word_len > width {
+            out.push(current.replace('\u{a0}', " "));
+            current = rest.to_string();
+            len = current.chars().count();
+            empty = true;
+        }
+        if !empty {
+            current.push(' ');
+            len += 1;
+        }
+        current.push_str(word);
+        len += word_len;
+        empty = false;
+    }
+    if !empty {
+        out.push(current.replace('\u{a0}', " "));
+    }
+}
+
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| entity(&rest[1..end + 1]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "euro" => '€',
+        "bull" => '•',
+        _ => return None,
+    })
+}
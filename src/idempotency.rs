@@ -0,0 +1,258 @@
+//! Suppressing duplicate sends with idempotency keys.
+//!
+//! An [`Email`](crate::Email) built with
+//! [`EmailBuilder::idempotency_key`](crate::EmailBuilder::idempotency_key)
+//! is only delivered once per key within the sender's
+//! [`idempotency_window`](crate::EmailSenderBuilder::idempotency_window).
+//! A second send with the same key fails with
+//! [`MailkitError::Duplicate`] without contacting the server, so a handler
+//! that retries after a timeout does not mail the user twice.
+//!
+//! Keys are recorded in an [`IdempotencyStore`]. [`MemoryStore`] is the
+//! default and forgets keys on restart; [`FileStore`] keeps them in a
+//! directory shared by every process using it.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+/// How long a [`FileStore`] lock file may exist before it is considered
+/// left behind by a crashed process.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+use crate::email_sender::MailkitError;
+
+/// Records which idempotency keys have been sent.
+///
+/// A key is inserted before its message is sent and removed again if the
+/// send fails, so a later retry can deliver it.
+pub trait IdempotencyStore: Send + Sync {
+    /// Records `key` for `window`. Returns `false` if it is already recorded
+    /// and has not expired.
+    fn insert(&self, key: &str, window: Duration) -> Result<bool, MailkitError>;
+
+    /// Forgets `key`.
+    fn remove(&self, key: &str) -> Result<(), MailkitError>;
+}
+
+/// Keeps keys in memory, for the lifetime of the sender.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    keys: HashMap<String, Instant>,
+    /// Expired keys are swept once the map grows to this size.
+    prune_at: usize,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn insert(&self, key: &str, window: Duration) -> Result<bool, MailkitError> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.keys.len() >= state.prune_at {
+            state.keys.retain(|_, expires| *expires > now);
+            state.prune_at = (state.keys.len() * 2).max(1024);
+        }
+        if state.keys.get(key).is_some_and(|expires| *expires > now) {
+            return Ok(false);
+        }
+        state.keys.insert(key.to_string(), now + window);
+        Ok(true)
+    }
+
+    fn remove(&self, key: &str) -> Result<(), MailkitError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.keys.remove(key);
+        Ok(())
+    }
+}
+
+/// Keeps keys as files in a directory, so they survive restarts and are
+/// shared between processes.
+///
+/// Each key is a small file named after the SHA-256 of the key. Records are
+/// written to a temporary file and linked into place, so they are never
+/// seen half written and only one of several concurrent inserts of the
+/// same key succeeds, even when they replace an expired record. Expired or
+/// unreadable records are replaced when their key is sent again; call
+/// [`FileStore::prune`] now and then to remove the rest.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Stores keys in `dir`, which is created on first use.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Deletes expired and unreadable keys and returns how many were
+    /// removed.
+    pub fn prune(&self) -> Result<usize, MailkitError> {
+        let now = now_millis();
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "key") {
+                continue;
+            }
+            let stale = |path: &Path| read_record(path).is_none_or(|(expires, _)| expires <= now);
+            if !stale(&path) {
+                continue;
+            }
+            // skip records an insert is replacing right now
+            let lock = path.with_extension("lock");
+            if OpenOptions::new().write(true).create_new(true).open(&lock).is_ok() {
+                if stale(&path) && fs::remove_file(&path).is_ok() {
+                    removed += 1;
+                }
+                let _ = fs::remove_file(&lock);
+            }
+        }
+        Ok(removed)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let digest: String = Sha256::digest(key.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}.key", digest))
+    }
+
+    /// A file name next to `path`, unique to this process and call.
+    fn temp_path(&self, path: &Path) -> PathBuf {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// Writes a record to a temporary file next to `path`.
+    fn write_temp(&self, path: &Path, record: &str) -> Result<PathBuf, MailkitError> {
+        let tmp = self.temp_path(path);
+        let mut file = File::create(&tmp)?;
+        file.write_all(record.as_bytes())?;
+        file.sync_all()?;
+        Ok(tmp)
+    }
+
+    /// Links `tmp` into place at `path`, replacing an expired or unreadable
+    /// record for the same key.
+    fn link(&self, key: &str, tmp: &Path, path: &Path) -> Result<bool, MailkitError> {
+        loop {
+            // linking fails if the record exists, unlike renaming
+            match fs::hard_link(tmp, path) {
+                Ok(()) => return Ok(true),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+            match read_record(path) {
+                Some((until, stored)) if stored == key && until > now_millis() => return Ok(false),
+                Some((_, stored)) if stored != key => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("{} holds a different idempotency key", path.display()),
+                    )
+                    .into());
+                }
+                _ => {}
+            }
+            // Expired, or left unreadable by a crash. Only the holder of the
+            // lock file removes it; anyone else waits and tries again.
+            let lock = path.with_extension("lock");
+            match OpenOptions::new().write(true).create_new(true).open(&lock) {
+                Ok(_) => {
+                    let linked = self.replace_stale(key, tmp, path);
+                    let _ = fs::remove_file(&lock);
+                    return linked;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    // a holder that crashed never releases the lock
+                    let abandoned = fs::metadata(&lock)
+                        .and_then(|m| m.modified())
+                        .is_ok_and(|t| t.elapsed().unwrap_or_default() > LOCK_TIMEOUT);
+                    if abandoned {
+                        let _ = fs::remove_file(&lock);
+                    } else {
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Replaces the record at `path` with `tmp` if it is still expired or
+    /// unreadable. Must be called with the key's lock file held.
+    fn replace_stale(&self, key: &str, tmp: &Path, path: &Path) -> Result<bool, MailkitError> {
+        if read_record(path).is_some_and(|(until, stored)| stored != key || until > now_millis()) {
+            return Ok(false);
+        }
+        match fs::remove_file(path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        // an insert that got in after the removal wins instead
+        match fs::hard_link(tmp, path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl IdempotencyStore for FileStore {
+    fn insert(&self, key: &str, window: Duration) -> Result<bool, MailkitError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let expires = now_millis() + window.as_millis() as u64;
+        let tmp = self.write_temp(&path, &format!("{}\n{}", expires, key))?;
+        let inserted = self.link(key, &tmp, &path);
+        let _ = fs::remove_file(&tmp);
+        inserted
+    }
+
+    fn remove(&self, key: &str) -> Result<(), MailkitError> {
+        let path = self.path(key);
+        if read_record(&path).is_some_and(|(_, stored)| stored == key) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Expiry in Unix milliseconds and the key, or `None` if the file is
+/// missing or corrupt.
+fn read_record(path: &Path) -> Option<(u64, String)> {
+    let data = fs::read_to_string(path).ok()?;
+    let (expires, key) = data.split_once('\n')?;
+    Some((expires.parse().ok()?, key.to_string()))
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
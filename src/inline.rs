@@ -0,0 +1,150 @@
+//! Images embedded in HTML bodies and referenced by `cid:` URLs.
+//!
+//! Add them with [`EmailBuilder::inline_image`](crate::EmailBuilder::inline_image)
+//! or [`EmailBuilder::inline_image_bytes`](crate::EmailBuilder::inline_image_bytes),
+//! or let a template register them:
+//!
+//! ```html
+//! <img src="{{ inline_image(path="assets/logo.png") }}" alt="Logo">
+//! ```
+//!
+//! The HTML part and its images are sent together as `multipart/related`.
+//! Template paths are resolved like attachment paths, relative to the
+//! working directory.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+use lettre::message::{Attachment, SinglePart};
+use tera::{Tera, Value};
+
+use crate::attachment::parse_content_type;
+use crate::email_sender::MailkitError;
+
+/// An image shown inside the HTML body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    content_id: String,
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Path(String),
+    Bytes { data: Vec<u8>, content_type: String },
+}
+
+impl InlineImage {
+    pub(crate) fn from_path(content_id: String, path: String) -> Self {
+        Self {
+            content_id,
+            source: Source::Path(path),
+        }
+    }
+
+    pub(crate) fn from_bytes(content_id: String, data: Vec<u8>, content_type: String) -> Self {
+        Self {
+            content_id,
+            source: Source::Bytes { data, content_type },
+        }
+    }
+
+    /// The id the HTML refers to as `cid:<content_id>`.
+    pub fn content_id(&self) -> &str {
+        &self.content_id
+    }
+
+    /// The file the image is read from, if it was not given as bytes.
+    pub fn path(&self) -> Option<&str> {
+        match &self.source {
+            Source::Path(path) => Some(path),
+            Source::Bytes { .. } => None,
+        }
+    }
+
+    /// Checks the Content-ID and content type before anything is read.
+    pub(crate) fn validate(&self) -> Result<(), MailkitError> {
+        let valid = !self.content_id.is_empty()
+            && self
+                .content_id
+                .bytes()
+                .all(|b| b.is_ascii_graphic() && b != b'<' && b != b'>');
+        if !valid {
+            return Err(MailkitError::Validation(format!("Invalid Content-ID: {}", self.content_id)));
+        }
+        if let Source::Bytes { content_type, .. } = &self.source {
+            parse_content_type(content_type)?;
+        }
+        Ok(())
+    }
+
+    /// Builds the MIME part, reading the file from disk.
+    pub(crate) fn part(&self) -> Result<SinglePart, MailkitError> {
+        match &self.source {
+            Source::Path(path) => self.build(std::fs::read(path)?, &guess(path)),
+            Source::Bytes { data, content_type } => self.build(data.clone(), content_type),
+        }
+    }
+
+    /// Async version of [`InlineImage::part`] using `tokio::fs`.
+    pub(crate) async fn part_async(&self) -> Result<SinglePart, MailkitError> {
+        match &self.source {
+            Source::Path(path) => self.build(tokio::fs::read(path).await?, &guess(path)),
+            Source::Bytes { data, content_type } => self.build(data.clone(), content_type),
+        }
+    }
+
+    fn build(&self, data: Vec<u8>, content_type: &str) -> Result<SinglePart, MailkitError> {
+        Ok(Attachment::new_inline(self.content_id.clone()).body(data, parse_content_type(content_type)?))
+    }
+}
+
+fn guess(path: &str) -> String {
+    mime_guess::from_path(path).first_or_octet_stream().essence_str().to_string()
+}
+
+thread_local! {
+    /// Images referenced by the template being rendered on this thread.
+    static COLLECTED: RefCell<Option<Vec<InlineImage>>> = const { RefCell::new(None) };
+}
+
+/// Runs `render` and returns the images its templates registered with
+/// `inline_image`.
+pub(crate) fn collect<T>(render: impl FnOnce() -> T) -> (T, Vec<InlineImage>) {
+    let outer = COLLECTED.with(|c| c.replace(Some(Vec::new())));
+    let out = render();
+    let images = COLLECTED.with(|c| c.replace(outer)).unwrap_or_default();
+    (out, images)
+}
+
+/// Adds the `inline_image` function to `tera`.
+pub(crate) fn register(tera: &mut Tera) {
+    tera.register_function("inline_image", inline_image);
+}
+
+fn inline_image(args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let path = args
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| tera::Error::msg("inline_image requires a `path` argument"))?;
+    if !Path::new(path).is_file() {
+        return Err(tera::Error::msg(format!("inline image not found: {}", path)));
+    }
+    // the same file always gets the same id, so it is only attached once
+    let content_id = format!("{:016x}@mailkit", fnv1a(path.as_bytes()));
+    COLLECTED.with(|c| {
+        if let Some(images) = c.borrow_mut().as_mut()
+            && !images.iter().any(|i| i.content_id == content_id)
+        {
+            images.push(InlineImage::from_path(content_id.clone(), path.to_string()));
+        }
+    });
+    Ok(Value::String(format!("cid:{}", content_id)))
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
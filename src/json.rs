@@ -2,10 +2,10 @@
 //!
 //! The [`json!`] macro can be used to easily build `JsonValue` structures.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JsonValue {
     Null,
@@ -23,6 +23,13 @@ impl JsonValue {
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
 }
 
 impl From<bool> for JsonValue {
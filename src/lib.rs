@@ -1,15 +1,36 @@
 //! MailKit is a small library for sending emails.
 //!
 //! It exposes a simple logger, a JSON helper type and an [`EmailSender`]
-//! which provides sync and async email sending. See the individual modules
-//! for more details.
+//! which provides sync and async email sending through pluggable
+//! [`transport`]s. See the individual modules for more details.
 
 #![forbid(unsafe_code)]
 
+pub mod attachment;
+pub mod bulk;
+pub mod dkim;
 pub mod email_sender;
+pub mod html_text;
+pub mod idempotency;
+pub mod inline;
+pub mod limit;
+pub mod outbox;
+pub mod pool;
+pub mod recipients;
+pub mod retry;
+pub mod transport;
 pub mod simple_logger;
 pub mod json;
+mod mime;
+mod sniff;
 
-pub use email_sender::{EmailSender, SendAgent};
+pub use attachment::AttachmentSource;
+pub use bulk::{BulkMode, BulkReport, CancelToken};
+pub use email_sender::{Delivery, Email, EmailBuilder, EmailSender, EmailSenderBuilder, ErrorKind, MailkitError, TlsMode};
+#[allow(deprecated)]
+pub use email_sender::SendAgent;
 pub use simple_logger::SimpleLogger;
 pub use json::JsonValue;
+pub use limit::RateLimit;
+pub use pool::PoolConfig;
+pub use retry::RetryPolicy;
@@ -0,0 +1,228 @@
+//! Concurrency and rate limits for outgoing mail.
+//!
+//! [`EmailSenderBuilder::max_in_flight`](crate::EmailSenderBuilder::max_in_flight)
+//! caps how many messages are handed to the transport at the same time and
+//! [`EmailSenderBuilder::rate_limit`](crate::EmailSenderBuilder::rate_limit)
+//! adds token buckets such as "10 per second" or "500 per day". Both apply
+//! to every send method of the sender, single and bulk, sync and async.
+//!
+//! Sync and async sends share the same slots. A sync send waiting for a
+//! slot or token blocks its thread. On a multi-thread tokio runtime it
+//! hands the worker's other tasks to another thread first, but on a
+//! current-thread runtime it stalls the whole runtime, so call the async
+//! methods from inside a tokio runtime.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::sync::Notify;
+
+/// How many sends bulk and stream methods and the outbox worker start at
+/// once when [`max_in_flight`](crate::EmailSenderBuilder::max_in_flight) is
+/// not set.
+pub(crate) const DEFAULT_CONCURRENCY: usize = 16;
+
+/// A token bucket allowing `max` messages per `per`.
+///
+/// The bucket starts full, so up to `max` messages go out at once and
+/// later ones are spread evenly over the interval. Every delivery attempt,
+/// including retries, takes one token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    max: u32,
+    per: Duration,
+}
+
+impl RateLimit {
+    /// `max` messages per `per`. A `max` of `0` is treated as `1`.
+    pub fn new(max: u32, per: Duration) -> Self {
+        Self { max: max.max(1), per }
+    }
+
+    pub fn per_second(max: u32) -> Self {
+        Self::new(max, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(max: u32) -> Self {
+        Self::new(max, Duration::from_secs(60))
+    }
+
+    pub fn per_hour(max: u32) -> Self {
+        Self::new(max, Duration::from_secs(60 * 60))
+    }
+
+    pub fn per_day(max: u32) -> Self {
+        Self::new(max, Duration::from_secs(24 * 60 * 60))
+    }
+}
+
+struct Bucket {
+    capacity: f64,
+    per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        let capacity = limit.max as f64;
+        Self {
+            capacity,
+            per_sec: capacity / limit.per.as_secs_f64().max(f64::EPSILON),
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Refills the bucket up to `now` and returns how long until it holds
+    /// a whole token.
+    fn wait(&mut self, now: Instant) -> Duration {
+        let refill = now.saturating_duration_since(self.updated).as_secs_f64() * self.per_sec;
+        self.tokens = (self.tokens + refill).min(self.capacity);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.per_sec)
+        }
+    }
+}
+
+/// Send slots that sync callers wait for on a condition variable and async
+/// callers on a [`Notify`], so neither blocks the other's executor.
+struct Slots {
+    max: usize,
+    used: Mutex<usize>,
+    freed: Condvar,
+    freed_async: Notify,
+}
+
+impl Slots {
+    fn try_take(&self) -> bool {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let free = *used < self.max;
+        if free {
+            *used += 1;
+        }
+        free
+    }
+}
+
+/// A send slot, released when dropped.
+pub(crate) struct Permit {
+    slots: Arc<Slots>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.slots.used.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        // wake one waiter of each kind; whoever loses the race waits again
+        self.slots.freed.notify_one();
+        self.slots.freed_async.notify_one();
+    }
+}
+
+/// Shared limits of one [`EmailSender`](crate::EmailSender).
+pub(crate) struct Limiter {
+    slots: Option<Arc<Slots>>,
+    buckets: Vec<Mutex<Bucket>>,
+}
+
+impl Limiter {
+    pub(crate) fn new(max_in_flight: Option<usize>, limits: &[RateLimit]) -> Self {
+        Self {
+            slots: max_in_flight.map(|n| {
+                Arc::new(Slots {
+                    max: n.max(1),
+                    used: Mutex::new(0),
+                    freed: Condvar::new(),
+                    freed_async: Notify::new(),
+                })
+            }),
+            buckets: limits.iter().map(|l| Mutex::new(Bucket::new(*l))).collect(),
+        }
+    }
+
+    /// How many sends a bulk or stream method should start at once.
+    pub(crate) fn concurrency(&self) -> usize {
+        self.slots.as_ref().map_or(DEFAULT_CONCURRENCY, |s| s.max)
+    }
+
+    /// Takes a token from every bucket if all of them have one. Otherwise
+    /// takes nothing and returns how long to wait before trying again, so a
+    /// caller that gives up while waiting costs no tokens.
+    fn try_take(&self) -> Option<Duration> {
+        let now = Instant::now();
+        // lock every bucket so the tokens are taken from all or none
+        let mut buckets: Vec<_> = self
+            .buckets
+            .iter()
+            .map(|b| b.lock().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        let wait = buckets.iter_mut().map(|b| b.wait(now)).max().unwrap_or(Duration::ZERO);
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+        for bucket in &mut buckets {
+            bucket.tokens -= 1.0;
+        }
+        None
+    }
+
+    /// Blocks until a send slot and a rate token are available. The slot is
+    /// held until the returned permit is dropped.
+    ///
+    /// On a worker of a multi-thread tokio runtime the wait runs in
+    /// [`block_in_place`](tokio::task::block_in_place), so the worker's other
+    /// tasks move to another thread instead of stalling behind it.
+    pub(crate) fn acquire(&self) -> Option<Permit> {
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| self.acquire_blocking())
+            }
+            _ => self.acquire_blocking(),
+        }
+    }
+
+    fn acquire_blocking(&self) -> Option<Permit> {
+        let permit = self.slots.clone().map(|slots| {
+            let mut used = slots.used.lock().unwrap_or_else(|e| e.into_inner());
+            while *used >= slots.max {
+                used = slots.freed.wait(used).unwrap_or_else(|e| e.into_inner());
+            }
+            *used += 1;
+            drop(used);
+            Permit { slots }
+        });
+        while let Some(wait) = self.try_take() {
+            std::thread::sleep(wait);
+        }
+        permit
+    }
+
+    /// Async version of [`Limiter::acquire`].
+    pub(crate) async fn acquire_async(&self) -> Option<Permit> {
+        let permit = match self.slots.clone() {
+            Some(slots) => {
+                loop {
+                    // register before checking so a release in between is
+                    // not missed
+                    let freed = slots.freed_async.notified();
+                    tokio::pin!(freed);
+                    freed.as_mut().enable();
+                    if slots.try_take() {
+                        break;
+                    }
+                    freed.await;
+                }
+                Some(Permit { slots })
+            }
+            None => None,
+        };
+        while let Some(wait) = self.try_take() {
+            tokio::time::sleep(wait).await;
+        }
+        permit
+    }
+}
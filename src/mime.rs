@@ -0,0 +1,361 @@
+//! Minimal MIME parsing used to inspect messages after they were built.
+//!
+//! Only what MailKit itself produces is supported: folded headers, RFC 2047
+//! encoded words, nested multiparts and the `base64`, `quoted-printable`,
+//! `7bit` and `8bit` transfer encodings.
+
+/// A parsed MIME entity with its decoded body.
+#[derive(Debug, Clone)]
+pub(crate) struct Part {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    children: Vec<Part>,
+}
+
+impl Part {
+    /// Parses a whole message. The line ending terminating the message is
+    /// not part of the body.
+    pub(crate) fn parse(raw: &[u8]) -> Part {
+        Part::parse_entity(trim_eol(raw))
+    }
+
+    fn parse_entity(raw: &[u8]) -> Part {
+        let (head, body) = split_head(raw);
+        let headers = parse_headers(head);
+        let mut part = Part {
+            headers,
+            body: Vec::new(),
+            children: Vec::new(),
+        };
+
+        let (essence, params) = part.content_type();
+        if essence.starts_with("multipart/") {
+            if let Some(boundary) = param(&params, "boundary") {
+                part.children = split_multipart(body, &boundary)
+                    .into_iter()
+                    .map(Part::parse_entity)
+                    .collect();
+            }
+        } else {
+            let encoding = part.header("Content-Transfer-Encoding").unwrap_or_default();
+            part.body = decode_body(&encoding, body);
+        }
+        part
+    }
+
+    /// Headers in message order, with encoded words decoded.
+    pub(crate) fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// First header called `name`, compared case-insensitively.
+    pub(crate) fn header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.clone())
+    }
+
+    /// Lowercased `type/subtype` and the raw parameter list.
+    pub(crate) fn content_type(&self) -> (String, Vec<(String, String)>) {
+        let value = self.header("Content-Type").unwrap_or_else(|| "text/plain".into());
+        let (essence, params) = split_params(&value);
+        (essence.to_ascii_lowercase(), params)
+    }
+
+    /// Whether the part has `Content-Disposition: attachment`.
+    pub(crate) fn is_attachment(&self) -> bool {
+        self.disposition().is_some_and(|(kind, _)| kind == "attachment")
+    }
+
+    fn disposition(&self) -> Option<(String, Vec<(String, String)>)> {
+        let value = self.header("Content-Disposition")?;
+        let (kind, params) = split_params(&value);
+        Some((kind.to_ascii_lowercase(), params))
+    }
+
+    /// File name from `Content-Disposition` or the `name` parameter of
+    /// `Content-Type`.
+    pub(crate) fn filename(&self) -> Option<String> {
+        let from_disposition = self
+            .disposition()
+            .and_then(|(_, params)| param(&params, "filename"));
+        from_disposition.or_else(|| param(&self.content_type().1, "name"))
+    }
+
+    /// Depth-first iterator over this part and all nested parts.
+    pub(crate) fn walk(&self) -> Vec<&Part> {
+        let mut out = vec![self];
+        for child in &self.children {
+            out.extend(child.walk());
+        }
+        out
+    }
+
+    /// Decoded body of the first non-attachment part of type `essence`.
+    pub(crate) fn find_text(&self, essence: &str) -> Option<String> {
+        self.walk()
+            .into_iter()
+            .find(|p| p.children.is_empty() && !p.is_attachment() && p.content_type().0 == essence)
+            .map(|p| String::from_utf8_lossy(&p.body).into_owned())
+    }
+}
+
+fn split_head(raw: &[u8]) -> (&[u8], &[u8]) {
+    if let Some(pos) = find(raw, b"\r\n\r\n") {
+        (&raw[..pos], &raw[pos + 4..])
+    } else if let Some(pos) = find(raw, b"\n\n") {
+        (&raw[..pos], &raw[pos + 2..])
+    } else {
+        (raw, &[])
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_headers(head: &[u8]) -> Vec<(String, String)> {
+    let text = String::from_utf8_lossy(head);
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    for (_, value) in &mut headers {
+        *value = decode_words(value);
+    }
+    headers
+}
+
+fn split_params(value: &str) -> (String, Vec<(String, String)>) {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ';' if !quoted => pieces.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    pieces.push(current);
+
+    let essence = pieces.remove(0).trim().to_string();
+    let params = pieces
+        .into_iter()
+        .filter_map(|p| {
+            let (k, v) = p.split_once('=')?;
+            Some((k.trim().to_ascii_lowercase(), v.trim().trim_matches('"').to_string()))
+        })
+        .collect();
+    (essence, params)
+}
+
+/// Looks up `name`, also accepting the RFC 2231 `name*` form.
+fn param(params: &[(String, String)], name: &str) -> Option<String> {
+    let star = format!("{}*", name);
+    params.iter().find_map(|(k, v)| {
+        if k == name {
+            Some(v.clone())
+        } else if *k == star {
+            let encoded = v.splitn(3, '\'').nth(2).unwrap_or(v);
+            Some(percent_decode(encoded))
+        } else {
+            None
+        }
+    })
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Some(b) = hex_pair(bytes[i + 1], bytes[i + 2])
+        {
+            out.push(b);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let closing = format!("{}--", delimiter);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+    while pos < body.len() {
+        let end = body[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(body.len(), |i| pos + i + 1);
+        let line = trim_eol(&body[pos..end]);
+        if line.starts_with(delimiter.as_bytes()) {
+            if let Some(s) = start {
+                parts.push(trim_eol(&body[s..pos]));
+            }
+            if line == closing.as_bytes() {
+                return parts;
+            }
+            start = Some(end);
+        }
+        pos = end;
+    }
+    parts
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn decode_body(encoding: &str, body: &[u8]) -> Vec<u8> {
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "base64" => base64_decode(body),
+        "quoted-printable" => qp_decode(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+pub(crate) fn base64_decode(input: &[u8]) -> Vec<u8> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &c in input {
+        if c == b'=' {
+            break;
+        }
+        let Some(v) = value(c) else { continue };
+        acc = (acc << 6) | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    out
+}
+
+pub(crate) fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn hex_pair(hi: u8, lo: u8) -> Option<u8> {
+    let hi = (hi as char).to_digit(16)?;
+    let lo = (lo as char).to_digit(16)?;
+    Some((hi * 16 + lo) as u8)
+}
+
+/// Decodes quoted-printable; `header` also maps `_` to a space (RFC 2047).
+fn qp_decode(input: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'=' if i + 1 < input.len() && input[i + 1] == b'\n' => i += 2,
+            b'=' if i + 2 < input.len() && input[i + 1] == b'\r' && input[i + 2] == b'\n' => i += 3,
+            b'=' if i + 2 < input.len() => match hex_pair(input[i + 1], input[i + 2]) {
+                Some(b) => {
+                    out.push(b);
+                    i += 3;
+                }
+                None => {
+                    out.push(b'=');
+                    i += 1;
+                }
+            },
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Decodes RFC 2047 encoded words such as `=?utf-8?b?SGk=?=`.
+fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut last_was_word = false;
+    while let Some(start) = rest.find("=?") {
+        let Some(decoded) = decode_word(&rest[start..]) else {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            last_was_word = false;
+            continue;
+        };
+        let between = &rest[..start];
+        // whitespace between adjacent encoded words is dropped
+        if !(last_was_word && between.trim().is_empty()) {
+            out.push_str(between);
+        }
+        out.push_str(&decoded.0);
+        rest = &rest[start + decoded.1..];
+        last_was_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_word(s: &str) -> Option<(String, usize)> {
+    let inner = s.strip_prefix("=?")?;
+    let mut fields = inner.splitn(3, '?');
+    let _charset = fields.next()?;
+    let encoding = fields.next()?;
+    let remainder = fields.next()?;
+    let end = remainder.find("?=")?;
+    let text = &remainder[..end];
+    let bytes = match encoding.to_ascii_lowercase().as_str() {
+        "b" => base64_decode(text.as_bytes()),
+        "q" => qp_decode(text.as_bytes(), true),
+        _ => return None,
+    };
+    let consumed = 2 + (inner.len() - remainder.len()) + end + 2;
+    Some((String::from_utf8_lossy(&bytes).into_owned(), consumed))
+}
@@ -0,0 +1,465 @@
+//! Durable outbox for mail that must survive an unreachable server or a
+//! crash.
+//!
+//! [`EmailSender::enqueue`](crate::EmailSender::enqueue) writes the
+//! finished message to a spool directory and returns immediately. A worker
+//! started with
+//! [`EmailSender::spawn_outbox_worker`](crate::EmailSender::spawn_outbox_worker)
+//! delivers queued messages in the background, reschedules transient
+//! failures with the outbox's [`RetryPolicy`] and moves messages that fail
+//! permanently or run out of attempts to a dead-letter directory.
+//!
+//! Messages can also be scheduled for later with
+//! [`EmailSender::enqueue_at`](crate::EmailSender::enqueue_at) and
+//! [`EmailSender::enqueue_after`](crate::EmailSender::enqueue_after), and
+//! cancelled or rescheduled by id until the worker picks them up.
+//!
+//! The spool directory has four subdirectories:
+//!
+//! | Directory  | Contents                                            |
+//! |------------|-----------------------------------------------------|
+//! | `tmp/`     | entries being written, removed on startup           |
+//! | `queue/`   | entries waiting for their next attempt              |
+//! | `sending/` | entries claimed by a worker, requeued on startup    |
+//! | `dead/`    | entries that will not be retried                    |
+//!
+//! Each entry is one `<id>.msg` file: a line of JSON with the envelope and
+//! attempt count, followed by the raw message. Entries are written to
+//! `tmp/` and renamed into place, so a crash never leaves a partial entry
+//! in `queue/`. A message whose send was interrupted by a crash is sent
+//! again after restart, so delivery is at least once.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lettre::Address;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::bulk::CancelToken;
+use crate::email_sender::MailkitError;
+use crate::retry::RetryPolicy;
+use crate::transport::Envelope;
+use crate::transport::file::unique_id;
+
+const TMP: &str = "tmp";
+const QUEUE: &str = "queue";
+const SENDING: &str = "sending";
+const DEAD: &str = "dead";
+
+/// A spool directory and the policy for retrying its messages.
+///
+/// ```
+/// use mailkit::outbox::Outbox;
+/// use mailkit::retry::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let outbox = Outbox::new("/var/spool/myapp/outbox").retry(
+///     RetryPolicy::new()
+///         .max_attempts(20)
+///         .base_delay(Duration::from_secs(60)),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    dir: PathBuf,
+    retry: RetryPolicy,
+    poll_interval: Duration,
+    wake: Arc<Notify>,
+}
+
+impl Outbox {
+    /// An outbox spooling into `dir`, which is created when the sender is
+    /// built.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            retry: RetryPolicy::new()
+                .max_attempts(10)
+                .base_delay(Duration::from_secs(30))
+                .max_delay(Duration::from_secs(60 * 60)),
+            poll_interval: Duration::from_secs(5),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// When and how often a message is retried before it is dead-lettered.
+    /// Defaults to 10 attempts starting 30 seconds apart, doubling up to an
+    /// hour.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// How often the worker looks for due messages when nothing was
+    /// enqueued. Defaults to 5 seconds.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Messages waiting to be sent, oldest first.
+    pub fn pending(&self) -> Result<Vec<OutboxEntry>, MailkitError> {
+        self.entries(QUEUE)
+    }
+
+    /// Messages that will not be retried, oldest first.
+    pub fn dead(&self) -> Result<Vec<OutboxEntry>, MailkitError> {
+        self.entries(DEAD)
+    }
+
+    /// Moves a dead message back into the queue with its attempt count
+    /// reset, to be sent on the worker's next pass.
+    pub fn requeue(&self, id: &str) -> Result<(), MailkitError> {
+        let (mut meta, raw) = read_entry(&self.path(DEAD, id))?;
+        meta.attempts = 0;
+        meta.next_attempt = now_millis();
+        self.write(QUEUE, id, &meta, &raw)?;
+        fs::remove_file(self.path(DEAD, id))?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Removes a queued message so it is never sent. Returns `false` if
+    /// there is no such message, e.g. because it was already sent.
+    pub fn cancel(&self, id: &str) -> Result<bool, MailkitError> {
+        match fs::remove_file(self.path(QUEUE, id)) {
+            Ok(()) => {
+                crate::info!("Cancelled outbox message {}", id);
+                Ok(true)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Moves the next attempt of a queued message to `at`. Returns `false`
+    /// if there is no such message, e.g. because it is being sent.
+    pub fn reschedule(&self, id: &str, at: SystemTime) -> Result<bool, MailkitError> {
+        // claimed like a send, so a worker cannot pick it up meanwhile
+        let sending = self.path(SENDING, id);
+        if fs::rename(self.path(QUEUE, id), &sending).is_err() {
+            return Ok(false);
+        }
+        let (mut meta, raw) = read_entry(&sending)?;
+        meta.next_attempt = unix_millis(at);
+        self.write(QUEUE, id, &meta, &raw)?;
+        fs::remove_file(&sending)?;
+        self.wake.notify_one();
+        Ok(true)
+    }
+
+    /// Creates the spool directories and recovers from an earlier crash:
+    /// half-written entries are removed and claimed ones are requeued.
+    pub(crate) fn open(&self) -> Result<(), MailkitError> {
+        for sub in [TMP, QUEUE, SENDING, DEAD] {
+            fs::create_dir_all(self.dir.join(sub))?;
+        }
+        for path in list(&self.dir.join(TMP))? {
+            fs::remove_file(path)?;
+        }
+        for path in list(&self.dir.join(SENDING))? {
+            let queued = self.dir.join(QUEUE).join(path.file_name().unwrap_or_default());
+            if queued.exists() {
+                fs::remove_file(&path)?;
+            } else {
+                crate::warn!("Requeuing interrupted outbox message {}", path.display());
+                fs::rename(&path, queued)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Spools a formatted message due at `at` and wakes the worker.
+    pub(crate) fn push(
+        &self,
+        envelope: &Envelope,
+        message_id: &str,
+        raw: &[u8],
+        at: SystemTime,
+    ) -> Result<String, MailkitError> {
+        let id = unique_id();
+        let meta = Meta {
+            message_id: message_id.to_string(),
+            from: envelope.from().map(|a| a.to_string()),
+            to: envelope.to().iter().map(|a| a.to_string()).collect(),
+            attempts: 0,
+            next_attempt: unix_millis(at),
+            last_error: None,
+        };
+        self.write(QUEUE, &id, &meta, raw)?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+
+    /// Ids of queued messages that are due, oldest first, and how long until
+    /// the next one that is not.
+    pub(crate) fn due(&self) -> Result<(Vec<String>, Option<Duration>), MailkitError> {
+        let now = now_millis();
+        let mut due = Vec::new();
+        let mut next: Option<u64> = None;
+        for entry in self.entries(QUEUE)? {
+            if entry.meta.next_attempt <= now {
+                due.push(entry.id);
+            } else {
+                next = Some(next.map_or(entry.meta.next_attempt, |n| n.min(entry.meta.next_attempt)));
+            }
+        }
+        Ok((due, next.map(|n| Duration::from_millis(n - now))))
+    }
+
+    /// Takes a queued message for sending. Returns `None` if another worker
+    /// claimed it first.
+    pub(crate) fn claim(&self, id: &str) -> Result<Option<Claimed>, MailkitError> {
+        let sending = self.path(SENDING, id);
+        if fs::rename(self.path(QUEUE, id), &sending).is_err() {
+            return Ok(None);
+        }
+        match read_entry(&sending) {
+            Ok((meta, raw)) => match meta.envelope() {
+                Ok(envelope) => Ok(Some(Claimed {
+                    id: id.to_string(),
+                    meta,
+                    raw,
+                    envelope,
+                })),
+                Err(err) => {
+                    crate::error!("Outbox message {} has an invalid envelope: {}", id, err);
+                    let mut meta = meta;
+                    meta.last_error = Some(err.to_string());
+                    self.write(DEAD, id, &meta, &raw)?;
+                    fs::remove_file(&sending)?;
+                    Ok(None)
+                }
+            },
+            Err(err) => {
+                crate::error!("Outbox message {} is unreadable: {}", id, err);
+                fs::rename(&sending, self.path(DEAD, id))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Records the result of sending a claimed message.
+    pub(crate) fn finish(&self, claimed: Claimed, result: Result<String, MailkitError>) -> Result<(), MailkitError> {
+        let Claimed { id, mut meta, raw, .. } = claimed;
+        let sending = self.path(SENDING, &id);
+        meta.attempts += 1;
+        match result {
+            Ok(response) => {
+                crate::info!("Outbox message {} sent: {}", meta.message_id, response);
+                fs::remove_file(&sending)?;
+                return Ok(());
+            }
+            Err(err) if self.retry.should_retry(&err, meta.attempts) => {
+                let delay = self.retry.delay(meta.attempts);
+                crate::warn!(
+                    "Outbox message {} failed attempt {}: {}; retrying in {:?}",
+                    meta.message_id,
+                    meta.attempts,
+                    err,
+                    delay
+                );
+                meta.last_error = Some(err.to_string());
+                meta.next_attempt = now_millis() + delay.as_millis() as u64;
+                self.write(QUEUE, &id, &meta, &raw)?;
+            }
+            Err(err) => {
+                crate::error!(
+                    "Outbox message {} failed after {} attempts: {}",
+                    meta.message_id,
+                    meta.attempts,
+                    err
+                );
+                meta.last_error = Some(err.to_string());
+                self.write(DEAD, &id, &meta, &raw)?;
+            }
+        }
+        fs::remove_file(&sending)?;
+        Ok(())
+    }
+
+    pub(crate) fn poll(&self) -> Duration {
+        self.poll_interval
+    }
+
+    pub(crate) async fn woken(&self) {
+        self.wake.notified().await
+    }
+
+    fn path(&self, sub: &str, id: &str) -> PathBuf {
+        self.dir.join(sub).join(format!("{}.msg", id))
+    }
+
+    /// Writes an entry to `tmp/`, syncs it and renames it into `sub`.
+    fn write(&self, sub: &str, id: &str, meta: &Meta, raw: &[u8]) -> Result<(), MailkitError> {
+        let tmp = self.path(TMP, id);
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer(&mut file, meta).map_err(|e| MailkitError::Io(e.into()))?;
+        file.write_all(b"\n")?;
+        file.write_all(raw)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.path(sub, id))?;
+        Ok(())
+    }
+
+    fn entries(&self, sub: &str) -> Result<Vec<OutboxEntry>, MailkitError> {
+        let mut entries = Vec::new();
+        for path in list(&self.dir.join(sub))? {
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            // an entry claimed or moved since listing is skipped
+            let Ok(meta) = read_meta(&path) else { continue };
+            entries.push(OutboxEntry { id: id.to_string(), meta });
+        }
+        Ok(entries)
+    }
+}
+
+/// A queued or dead-lettered message.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    id: String,
+    meta: Meta,
+}
+
+impl OutboxEntry {
+    /// Id of the entry in the outbox, as returned by
+    /// [`EmailSender::enqueue`](crate::EmailSender::enqueue).
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn message_id(&self) -> &str {
+        &self.meta.message_id
+    }
+
+    pub fn from(&self) -> Option<&str> {
+        self.meta.from.as_deref()
+    }
+
+    pub fn to(&self) -> &[String] {
+        &self.meta.to
+    }
+
+    /// Delivery attempts made so far.
+    pub fn attempts(&self) -> u32 {
+        self.meta.attempts
+    }
+
+    /// When the message is due to be sent, or was due if it is dead.
+    pub fn next_attempt(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.meta.next_attempt)
+    }
+
+    /// Error of the most recent failed attempt.
+    pub fn last_error(&self) -> Option<&str> {
+        self.meta.last_error.as_deref()
+    }
+}
+
+/// First line of an entry file.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Meta {
+    message_id: String,
+    from: Option<String>,
+    to: Vec<String>,
+    attempts: u32,
+    /// Unix time in milliseconds.
+    next_attempt: u64,
+    last_error: Option<String>,
+}
+
+impl Meta {
+    fn envelope(&self) -> Result<Envelope, MailkitError> {
+        let from = self.from.as_deref().map(str::parse::<Address>).transpose()?;
+        let to = self
+            .to
+            .iter()
+            .map(|a| a.parse::<Address>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Envelope::new(from, to)?)
+    }
+}
+
+/// A message taken out of the queue by a worker.
+pub(crate) struct Claimed {
+    id: String,
+    meta: Meta,
+    raw: Vec<u8>,
+    envelope: Envelope,
+}
+
+impl Claimed {
+    pub(crate) fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    pub(crate) fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+/// Handle to a running outbox worker.
+///
+/// Dropping the handle leaves the worker running; call
+/// [`OutboxWorker::shutdown`] to stop it after its current pass.
+#[derive(Debug)]
+pub struct OutboxWorker {
+    pub(crate) cancel: CancelToken,
+    pub(crate) handle: JoinHandle<()>,
+}
+
+impl OutboxWorker {
+    /// Stops the worker and waits for messages it is sending to finish.
+    pub async fn shutdown(self) {
+        self.cancel.cancel();
+        if let Err(err) = self.handle.await {
+            crate::error!("Outbox worker panicked: {}", err);
+        }
+    }
+}
+
+fn list(dir: &Path) -> Result<Vec<PathBuf>, MailkitError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "msg") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn read_meta(path: &Path) -> Result<Meta, MailkitError> {
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(|e| MailkitError::Io(e.into()))
+}
+
+fn read_entry(path: &Path) -> Result<(Meta, Vec<u8>), MailkitError> {
+    let data = fs::read(path)?;
+    let split = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+    let meta = serde_json::from_slice(&data[..split]).map_err(|e| MailkitError::Io(e.into()))?;
+    let raw = data.get(split + 1..).unwrap_or_default().to_vec();
+    Ok((meta, raw))
+}
+
+fn now_millis() -> u64 {
+    unix_millis(SystemTime::now())
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
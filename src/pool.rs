@@ -0,0 +1,123 @@
+//! Reuse of SMTP connections across sends.
+//!
+//! [`EmailSender`](crate::EmailSender) keeps authenticated connections open
+//! after a message has been delivered and hands them to the next send
+//! instead of opening a new TCP+TLS+AUTH session every time. Configure the
+//! pool with [`PoolConfig`].
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::transport::TlsMode;
+
+/// Settings for the SMTP connection pool.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    max_idle: usize,
+    idle_timeout: Duration,
+    max_messages_per_connection: usize,
+}
+
+impl PoolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of connections kept open between sends. `0`
+    /// disables pooling. Defaults to `4`.
+    pub fn max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// How long an unused connection stays open. Defaults to 60 seconds.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Number of messages after which a connection is closed and replaced.
+    /// Defaults to `100`.
+    pub fn max_messages_per_connection(mut self, max: usize) -> Self {
+        self.max_messages_per_connection = max;
+        self
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle: 4,
+            idle_timeout: Duration::from_secs(60),
+            max_messages_per_connection: 100,
+        }
+    }
+}
+
+/// A connection checked out of, or about to be returned to, a pool.
+pub(crate) struct Pooled<C> {
+    pub(crate) conn: C,
+    pub(crate) tls: TlsMode,
+    pub(crate) sent: usize,
+    idle_since: Instant,
+}
+
+impl<C> Pooled<C> {
+    pub(crate) fn new(conn: C, tls: TlsMode) -> Self {
+        Self {
+            conn,
+            tls,
+            sent: 0,
+            idle_since: Instant::now(),
+        }
+    }
+}
+
+/// Idle connection storage. Closing connections is left to the caller so
+/// the same pool works for sync and async connections.
+pub(crate) struct ConnectionPool<C> {
+    config: PoolConfig,
+    idle: Mutex<Vec<Pooled<C>>>,
+}
+
+impl<C> ConnectionPool<C> {
+    pub(crate) fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Takes the most recently used idle connection opened with `tls`.
+    /// Connections that idled for too long are returned for closing.
+    pub(crate) fn take(&self, tls: TlsMode) -> (Option<Pooled<C>>, Vec<C>) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let (expired, fresh): (Vec<_>, Vec<_>) = idle
+            .drain(..)
+            .partition(|p| p.idle_since.elapsed() >= self.config.idle_timeout);
+        *idle = fresh;
+        let found = idle.iter().rposition(|p| p.tls == tls).map(|i| idle.remove(i));
+        (found, expired.into_iter().map(|p| p.conn).collect())
+    }
+
+    /// Returns a connection after a send. Gives the connection
+    /// back if it has to be closed instead.
+    pub(crate) fn put(&self, mut pooled: Pooled<C>) -> Option<C> {
+        if pooled.sent >= self.config.max_messages_per_connection {
+            return Some(pooled.conn);
+        }
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() >= self.config.max_idle {
+            return Some(pooled.conn);
+        }
+        pooled.idle_since = Instant::now();
+        idle.push(pooled);
+        None
+    }
+
+    /// Removes every idle connection.
+    pub(crate) fn drain(&self) -> Vec<C> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.drain(..).map(|p| p.conn).collect()
+    }
+}
@@ -0,0 +1,307 @@
+//! Loading mail merge recipients from CSV and JSON Lines files.
+//!
+//! Both formats produce `(address, context)` pairs ready for
+//! [`EmailSender::send_bulk_template`](crate::EmailSender::send_bulk_template).
+//! Every address is validated; rows that cannot be used are collected as
+//! [`RowError`]s with their line number instead of failing the whole load.
+//!
+//! ```no_run
+//! use mailkit::recipients::RecipientList;
+//! use mailkit::{BulkMode, EmailSender, json};
+//!
+//! # fn run(sender: &EmailSender) -> Result<(), mailkit::MailkitError> {
+//! let list = RecipientList::from_csv_path("customers.csv")?;
+//! for err in list.errors() {
+//!     eprintln!("skipping {}", err);
+//! }
+//! let report = sender.send_bulk_template(list, "Hi {{ first_name }}", "promo.html", &json!({}), BulkMode::ContinueOnError);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::email_sender::{EmailSender, MailkitError};
+use crate::json::JsonValue;
+
+/// Name of the column (CSV) or key (JSONL) holding the address.
+const EMAIL_FIELD: &str = "email";
+
+/// An address with its own template context, as consumed by the streaming
+/// bulk methods such as
+/// [`EmailSender::send_template_stream`](crate::EmailSender::send_template_stream).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipient {
+    address: String,
+    context: JsonValue,
+}
+
+impl Recipient {
+    pub fn new<S: Into<String>>(address: S, context: JsonValue) -> Self {
+        Self {
+            address: address.into(),
+            context,
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn context(&self) -> &JsonValue {
+        &self.context
+    }
+}
+
+impl From<String> for Recipient {
+    fn from(address: String) -> Self {
+        Recipient::new(address, JsonValue::Null)
+    }
+}
+
+impl From<&str> for Recipient {
+    fn from(address: &str) -> Self {
+        Recipient::new(address, JsonValue::Null)
+    }
+}
+
+impl From<(String, JsonValue)> for Recipient {
+    fn from((address, context): (String, JsonValue)) -> Self {
+        Recipient::new(address, context)
+    }
+}
+
+/// A row that was skipped while loading recipients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    line: usize,
+    message: String,
+}
+
+impl RowError {
+    /// 1-based line the row starts on.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Recipients and their template contexts, plus the rows that were
+/// rejected.
+///
+/// Iterating yields the `(address, context)` pairs, so a list can be passed
+/// straight to the bulk template methods.
+#[derive(Debug, Clone, Default)]
+pub struct RecipientList {
+    recipients: Vec<(String, JsonValue)>,
+    errors: Vec<RowError>,
+}
+
+impl RecipientList {
+    /// Parses CSV with a header row. One column must be called `email`
+    /// (case-insensitive); every column, including `email`, becomes a
+    /// string in the recipient's context under its header name.
+    pub fn from_csv_str(input: &str) -> Result<Self, MailkitError> {
+        let mut records = CsvRecords::new(input);
+        let header = loop {
+            match records.next() {
+                None => return Err(MailkitError::Validation("CSV input has no header row".into())),
+                Some((_, Ok(fields))) if is_blank(&fields) => continue,
+                Some((line, Ok(fields))) => break (line, fields),
+                Some((line, Err(msg))) => return Err(MailkitError::Validation(format!("line {}: {}", line, msg))),
+            }
+        };
+        let columns: Vec<String> = header.1.iter().map(|c| c.trim().to_string()).collect();
+        let email_index = columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(EMAIL_FIELD))
+            .ok_or_else(|| MailkitError::Validation("CSV header has no email column".into()))?;
+
+        let mut list = RecipientList::default();
+        for (line, record) in records {
+            let fields = match record {
+                Ok(fields) if is_blank(&fields) => continue,
+                Ok(fields) => fields,
+                Err(msg) => {
+                    list.reject(line, msg);
+                    continue;
+                }
+            };
+            if fields.len() != columns.len() {
+                list.reject(line, format!("expected {} fields, found {}", columns.len(), fields.len()));
+                continue;
+            }
+            let context: BTreeMap<String, JsonValue> = columns
+                .iter()
+                .cloned()
+                .zip(fields.iter().map(|f| JsonValue::String(f.clone())))
+                .collect();
+            list.accept(line, &fields[email_index], JsonValue::Object(context));
+        }
+        Ok(list)
+    }
+
+    /// Parses JSON Lines: one object per line with a string `email` key.
+    /// The whole object becomes the recipient's context. Blank lines are
+    /// ignored.
+    pub fn from_jsonl_str(input: &str) -> Result<Self, MailkitError> {
+        let mut list = RecipientList::default();
+        for (i, text) in input.lines().enumerate() {
+            let line = i + 1;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let value: JsonValue = match serde_json::from_str(text) {
+                Ok(value) => value,
+                Err(err) => {
+                    list.reject(line, format!("invalid JSON: {}", err));
+                    continue;
+                }
+            };
+            let Some(object) = value.as_object() else {
+                list.reject(line, "expected a JSON object".into());
+                continue;
+            };
+            let Some(email) = object.get(EMAIL_FIELD).and_then(JsonValue::as_str) else {
+                list.reject(line, format!("missing string field `{}`", EMAIL_FIELD));
+                continue;
+            };
+            let email = email.to_string();
+            list.accept(line, &email, value);
+        }
+        Ok(list)
+    }
+
+    pub fn from_csv_path<P: AsRef<Path>>(path: P) -> Result<Self, MailkitError> {
+        Self::from_csv_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_jsonl_path<P: AsRef<Path>>(path: P) -> Result<Self, MailkitError> {
+        Self::from_jsonl_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Async version of [`RecipientList::from_csv_path`].
+    pub async fn from_csv_path_async<P: AsRef<Path>>(path: P) -> Result<Self, MailkitError> {
+        Self::from_csv_str(&tokio::fs::read_to_string(path).await?)
+    }
+
+    /// Async version of [`RecipientList::from_jsonl_path`].
+    pub async fn from_jsonl_path_async<P: AsRef<Path>>(path: P) -> Result<Self, MailkitError> {
+        Self::from_jsonl_str(&tokio::fs::read_to_string(path).await?)
+    }
+
+    /// Valid recipients in file order. Addresses are trimmed and lowercased.
+    pub fn recipients(&self) -> &[(String, JsonValue)] {
+        &self.recipients
+    }
+
+    /// Rows that were skipped, in file order.
+    pub fn errors(&self) -> &[RowError] {
+        &self.errors
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipients.is_empty()
+    }
+
+    fn accept(&mut self, line: usize, email: &str, context: JsonValue) {
+        match EmailSender::check_email(email.trim()) {
+            Ok(address) => self.recipients.push((address, context)),
+            Err(_) => self.reject(line, format!("invalid email address: {:?}", email)),
+        }
+    }
+
+    fn reject(&mut self, line: usize, message: String) {
+        crate::warn!("Skipping recipient on line {}: {}", line, message);
+        self.errors.push(RowError { line, message });
+    }
+}
+
+impl IntoIterator for RecipientList {
+    type Item = (String, JsonValue);
+    type IntoIter = std::vec::IntoIter<(String, JsonValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.recipients.into_iter()
+    }
+}
+
+fn is_blank(fields: &[String]) -> bool {
+    fields.iter().all(|f| f.trim().is_empty())
+}
+
+/// RFC 4180 record reader yielding the starting line of each record.
+/// Quoted fields may contain commas, doubled quotes and line breaks.
+struct CsvRecords<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> CsvRecords<'a> {
+    fn new(input: &'a str) -> Self {
+        let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+        }
+    }
+}
+
+impl Iterator for CsvRecords<'_> {
+    type Item = (usize, Result<Vec<String>, String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chars.peek()?;
+        let start = self.line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut error = None;
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                '"' if quoted => {
+                    if self.chars.peek() == Some(&'"') {
+                        self.chars.next();
+                        field.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                }
+                '"' if field.is_empty() => quoted = true,
+                '\n' if quoted => {
+                    self.line += 1;
+                    field.push('\n');
+                }
+                '\r' if !quoted => {}
+                '\n' => {
+                    self.line += 1;
+                    break;
+                }
+                ',' if !quoted => fields.push(std::mem::take(&mut field)),
+                '"' => error = Some("unexpected quote in unquoted field".to_string()),
+                c => field.push(c),
+            }
+        }
+        if quoted {
+            error = Some("unterminated quoted field".into());
+        }
+        fields.push(field);
+        Some((start, error.map_or(Ok(fields), Err)))
+    }
+}
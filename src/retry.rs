@@ -0,0 +1,174 @@
+//! Retrying deliveries that failed for temporary reasons.
+//!
+//! A [`RetryPolicy`] set with
+//! [`EmailSenderBuilder::retry`](crate::EmailSenderBuilder::retry) makes
+//! [`EmailSender`](crate::EmailSender) resend a message after a transient
+//! failure, waiting an exponentially growing, jittered delay between
+//! attempts. Permanent failures such as `5xx` replies or invalid addresses
+//! are returned immediately.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::email_sender::MailkitError;
+
+/// Kinds of transient failure a [`RetryPolicy`] can retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryClass {
+    /// A `4xx` reply, e.g. greylisting or a full mailbox.
+    TemporaryRejection,
+    /// Connecting or an SMTP command took longer than its timeout.
+    Timeout,
+    /// The connection could not be opened or was reset.
+    Connection,
+}
+
+impl RetryClass {
+    /// The class of `err`, or `None` if retrying cannot help.
+    pub fn of(err: &MailkitError) -> Option<RetryClass> {
+        match err {
+            MailkitError::Smtp(e) if e.is_transient() => Some(RetryClass::TemporaryRejection),
+            MailkitError::Smtp(e) if e.is_timeout() => Some(RetryClass::Timeout),
+            MailkitError::Smtp(e) => {
+                let connection = !(e.is_permanent()
+                    || e.is_response()
+                    || e.is_client()
+                    || e.is_tls()
+                    || e.is_transport_shutdown());
+                connection.then_some(RetryClass::Connection)
+            }
+            MailkitError::Io(e) => {
+                use std::io::ErrorKind::*;
+                match e.kind() {
+                    TimedOut => Some(RetryClass::Timeout),
+                    ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected | BrokenPipe
+                    | UnexpectedEof => Some(RetryClass::Connection),
+                    _ => None,
+                }
+            }
+            // EX_TEMPFAIL from sysexits.h
+            MailkitError::Sendmail { status: Some(75), .. } => Some(RetryClass::TemporaryRejection),
+            _ => None,
+        }
+    }
+}
+
+/// How often and how patiently a failed delivery is retried.
+///
+/// The delay before retry `n` is `base_delay * 2^(n-1)`, capped at
+/// `max_delay`. With jitter enabled a random amount of up to half that
+/// delay is taken off, so many senders failing together do not retry in
+/// lockstep.
+///
+/// ```
+/// use mailkit::retry::{RetryClass, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new()
+///     .max_attempts(5)
+///     .base_delay(Duration::from_secs(1))
+///     .retry_on(&[RetryClass::TemporaryRejection]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    retry_on: Vec<RetryClass>,
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that never retries, the default of
+    /// [`EmailSender`](crate::EmailSender).
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Total number of attempts including the first one. Defaults to `3`.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry. Defaults to 500 milliseconds.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Upper bound for a single delay. Defaults to 30 seconds.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Whether delays are randomized. Defaults to `true`.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Failure classes that are retried. Defaults to all of them.
+    pub fn retry_on(mut self, classes: &[RetryClass]) -> Self {
+        self.retry_on = classes.to_vec();
+        self
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether `err` after attempt number `attempt` (starting at 1) should
+    /// be retried.
+    pub fn should_retry(&self, err: &MailkitError, attempt: u32) -> bool {
+        attempt < self.max_attempts && RetryClass::of(err).is_some_and(|c| self.retry_on.contains(&c))
+    }
+
+    /// Delay to wait after attempt number `attempt` (starting at 1) failed.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+        let half = delay / 2;
+        let nanos = half.as_nanos().min(u64::MAX as u128) as u64;
+        if nanos == 0 {
+            return delay;
+        }
+        delay - Duration::from_nanos(random() % (nanos + 1))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            retry_on: vec![RetryClass::TemporaryRejection, RetryClass::Timeout, RetryClass::Connection],
+        }
+    }
+}
+
+/// Cheap non-cryptographic randomness for jitter and message ids
+/// (splitmix64).
+pub(crate) fn random() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let mut z = STATE
+        .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+        .wrapping_add(seed);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
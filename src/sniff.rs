@@ -0,0 +1,150 @@
+//! Content type detection from the first bytes of a file.
+//!
+//! Only the types commonly sent as attachments are recognised: PDF, PNG,
+//! JPEG, GIF, ZIP and the Office formats built on it, legacy Office
+//! documents, iCalendar, CSV and plain text.
+
+/// Content type of `data`, or `None` if it is not recognised.
+pub(crate) fn sniff(data: &[u8]) -> Option<&'static str> {
+    const OLE: &[u8] = &[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+
+    if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        Some(zip_kind(data))
+    } else if data.starts_with(OLE) {
+        Some("application/x-ole-storage")
+    } else {
+        text_kind(data)
+    }
+}
+
+/// Tells Office Open XML and OpenDocument files apart from plain ZIPs.
+fn zip_kind(data: &[u8]) -> &'static str {
+    const ODF: &[(&[u8], &str)] = &[
+        (b"application/vnd.oasis.opendocument.text", "application/vnd.oasis.opendocument.text"),
+        (b"application/vnd.oasis.opendocument.spreadsheet", "application/vnd.oasis.opendocument.spreadsheet"),
+        (b"application/vnd.oasis.opendocument.presentation", "application/vnd.oasis.opendocument.presentation"),
+        (b"application/epub+zip", "application/epub+zip"),
+    ];
+    const OOXML: &[(&[u8], &str)] = &[
+        (b"word/document.xml", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+        (b"xl/workbook.xml", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (b"ppt/presentation.xml", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ];
+
+    // OpenDocument stores an uncompressed `mimetype` entry first
+    if data.get(30..38) == Some(&b"mimetype"[..]) {
+        let rest = &data[38..];
+        if let Some((_, mime)) = ODF.iter().find(|(m, _)| rest.starts_with(m)) {
+            return mime;
+        }
+    }
+    // entry names are stored uncompressed in the local and central headers
+    OOXML
+        .iter()
+        .find(|(name, _)| contains(data, name))
+        .map_or("application/zip", |(_, mime)| *mime)
+}
+
+fn text_kind(data: &[u8]) -> Option<&'static str> {
+    let sample = &data[..data.len().min(8192)];
+    if sample.is_empty() {
+        return None;
+    }
+    let text = match std::str::from_utf8(sample) {
+        Ok(text) => text,
+        // the sample may end inside a multi-byte character
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&sample[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    if text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+    {
+        return None;
+    }
+
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with("BEGIN:VCALENDAR") {
+        return Some("text/calendar");
+    }
+    let lines: Vec<&str> = text.lines().take(10).filter(|l| !l.trim().is_empty()).collect();
+    let commas = |line: &str| line.matches(',').count();
+    let csv = lines.len() >= 2 && commas(lines[0]) > 0 && lines.iter().all(|l| commas(l) == commas(lines[0]));
+    Some(if csv { "text/csv" } else { "text/plain" })
+}
+
+/// Whether a type guessed from the file name and one sniffed from the
+/// content describe the same kind of file. Generic containers match the
+/// specific formats built on them. Text content only tells that no binary
+/// signature was found, so it matches any type that is not binary, such as
+/// `message/rfc822` or `application/rtf`.
+pub(crate) fn compatible(guessed: &str, sniffed: &str) -> bool {
+    let guessed = guessed.to_ascii_lowercase();
+    if guessed == sniffed {
+        return true;
+    }
+    if family(sniffed) == "text" {
+        return !is_binary(&guessed);
+    }
+    let kind = family(&guessed);
+    kind == family(sniffed) && (is_generic(&guessed) || is_generic(sniffed))
+}
+
+fn family(mime: &str) -> &str {
+    let textual = mime.starts_with("text/")
+        || mime.ends_with("/xml")
+        || mime.ends_with("+xml")
+        || mime.ends_with("/json")
+        || mime.ends_with("+json")
+        || mime == "application/javascript";
+    if textual {
+        "text"
+    } else if mime == "application/zip"
+        || mime == "application/epub+zip"
+        || mime == "application/java-archive"
+        || mime.starts_with("application/vnd.openxmlformats-officedocument.")
+        || mime.starts_with("application/vnd.oasis.opendocument.")
+    {
+        "zip"
+    } else if matches!(
+        mime,
+        "application/x-ole-storage"
+            | "application/msword"
+            | "application/vnd.ms-excel"
+            | "application/vnd.ms-powerpoint"
+            | "application/vnd.ms-outlook"
+            | "application/x-msi"
+    ) {
+        "ole"
+    } else {
+        mime
+    }
+}
+
+/// Types whose content never passes as text.
+fn is_binary(mime: &str) -> bool {
+    let kind = family(mime);
+    kind == "zip"
+        || kind == "ole"
+        || mime == "application/pdf"
+        || (kind != "text"
+            && ["image/", "audio/", "video/", "font/"]
+                .iter()
+                .any(|prefix| mime.starts_with(prefix)))
+}
+
+fn is_generic(mime: &str) -> bool {
+    matches!(mime, "application/zip" | "application/x-ole-storage")
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
@@ -0,0 +1,140 @@
+//! Delivery backends used by [`EmailSender`](crate::EmailSender).
+//!
+//! The sender builds and validates messages, then hands the raw RFC 5322
+//! bytes and the SMTP envelope to a [`Transport`] (sync) or
+//! [`AsyncTransport`] (async). MailKit ships with:
+//!
+//! - [`SmtpTransport`] – pooled SMTP, the default
+//! - [`InMemoryTransport`] – keeps messages in memory, for tests
+//! - [`FileTransport`] – writes `.eml` files to a directory
+//! - [`SendmailTransport`] – pipes messages to a local `sendmail` binary
+//!
+//! Custom backends implement both traits and are installed with
+//! [`EmailSenderBuilder::transport`](crate::EmailSenderBuilder::transport).
+//! The built-in ones can also be chosen at runtime with a
+//! [`TransportConfig`], e.g. from `MAILKIT_TRANSPORT=file:/tmp/mail`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+pub use lettre::address::Envelope;
+
+use crate::email_sender::MailkitError;
+
+pub mod file;
+pub mod memory;
+pub mod sendmail;
+pub mod smtp;
+
+pub use file::FileTransport;
+pub use memory::InMemoryTransport;
+pub use sendmail::SendmailTransport;
+pub use smtp::{SmtpTransport, TlsMode};
+
+/// Built-in transport selected by configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportConfig {
+    /// Deliver over SMTP using the sender's SMTP settings.
+    Smtp,
+    /// [`FileTransport::new`] writing into the directory.
+    File(PathBuf),
+    /// [`FileTransport::maildir`] delivering into the Maildir.
+    Maildir(PathBuf),
+    /// [`SendmailTransport`] running the binary at the path.
+    Sendmail(PathBuf),
+}
+
+impl TransportConfig {
+    /// The configured transport, or `None` for SMTP which needs the rest
+    /// of the sender configuration.
+    pub(crate) fn build(&self) -> Option<(Arc<dyn Transport>, Arc<dyn AsyncTransport>)> {
+        fn pair<T: Transport + AsyncTransport + 'static>(t: T) -> (Arc<dyn Transport>, Arc<dyn AsyncTransport>) {
+            let t = Arc::new(t);
+            (t.clone(), t)
+        }
+
+        match self {
+            TransportConfig::Smtp => None,
+            TransportConfig::File(dir) => Some(pair(FileTransport::new(dir))),
+            TransportConfig::Maildir(dir) => Some(pair(FileTransport::maildir(dir))),
+            TransportConfig::Sendmail(command) => Some(pair(SendmailTransport::with_command(command))),
+        }
+    }
+}
+
+impl std::str::FromStr for TransportConfig {
+    type Err = MailkitError;
+
+    /// Parses the values accepted by `MAILKIT_TRANSPORT`: `smtp`,
+    /// `file:<dir>`, `maildir:<dir>` and `sendmail` (or `sendmail:<path>`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+        let dir = |arg: Option<&str>| match arg {
+            Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+            _ => Err(MailkitError::Validation(format!("Transport {} needs a directory", kind))),
+        };
+        match (kind.to_ascii_lowercase().as_str(), arg) {
+            ("smtp", None) => Ok(TransportConfig::Smtp),
+            ("file", arg) => Ok(TransportConfig::File(dir(arg)?)),
+            ("maildir", arg) => Ok(TransportConfig::Maildir(dir(arg)?)),
+            ("sendmail", None) => Ok(TransportConfig::Sendmail(sendmail::DEFAULT_SENDMAIL.into())),
+            ("sendmail", Some(path)) if !path.is_empty() => Ok(TransportConfig::Sendmail(path.into())),
+            _ => Err(MailkitError::Validation(format!("Invalid transport: {}", s))),
+        }
+    }
+}
+
+/// Blocking delivery of a finished message.
+pub trait Transport: Send + Sync {
+    /// Delivers `message` to the recipients of `envelope` and returns a
+    /// short description of the outcome, such as the server response.
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError>;
+
+    /// Releases any resources held by the transport.
+    fn close(&self) {}
+}
+
+/// Non-blocking delivery of a finished message.
+pub trait AsyncTransport: Send + Sync {
+    /// Async version of [`Transport::send_raw`].
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>>;
+
+    /// Async version of [`Transport::close`].
+    fn close_async(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        (**self).send_raw(envelope, message)
+    }
+
+    fn close(&self) {
+        (**self).close()
+    }
+}
+
+impl<T: AsyncTransport + ?Sized> AsyncTransport for std::sync::Arc<T> {
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>> {
+        (**self).send_raw_async(envelope, message)
+    }
+
+    fn close_async(&self) -> BoxFuture<'_, ()> {
+        (**self).close_async()
+    }
+}
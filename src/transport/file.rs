@@ -0,0 +1,180 @@
+//! Transport that writes each message to a directory.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future::BoxFuture;
+
+use super::{AsyncTransport, Envelope, Transport};
+use crate::email_sender::MailkitError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// `<id>.eml` plus a `<id>.json` envelope sidecar.
+    Flat,
+    /// Maildir: written to `tmp/`, then moved to `new/`.
+    Maildir,
+}
+
+/// Writes every message to a directory instead of sending it.
+///
+/// [`FileTransport::new`] stores flat `<id>.eml` files, each with a
+/// `<id>.json` sidecar holding the envelope:
+///
+/// ```json
+/// {"from":"noreply@example.com","to":["user@example.com"]}
+/// ```
+///
+/// [`FileTransport::maildir`] delivers into a Maildir so mail clients and
+/// tools like `mutt -f` can read the messages directly.
+#[derive(Debug, Clone)]
+pub struct FileTransport {
+    dir: PathBuf,
+    layout: Layout,
+}
+
+impl FileTransport {
+    /// Writes flat `.eml` files into `dir`, which is created on first use.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            layout: Layout::Flat,
+        }
+    }
+
+    /// Delivers into the Maildir at `dir`, creating `tmp/`, `new/` and
+    /// `cur/` on first use.
+    pub fn maildir<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            layout: Layout::Maildir,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_maildir(&self) -> bool {
+        self.layout == Layout::Maildir
+    }
+
+    fn plan(&self, envelope: &Envelope) -> Result<Planned, MailkitError> {
+        let id = unique_id();
+        match self.layout {
+            Layout::Flat => {
+                let sidecar = serde_json::to_vec(&EnvelopeRecord::from(envelope))
+                    .map_err(|e| MailkitError::Io(e.into()))?;
+                let path = self.dir.join(format!("{}.eml", id));
+                Ok(Planned {
+                    write_to: path.clone(),
+                    deliver_to: path,
+                    sidecar: Some((self.dir.join(format!("{}.json", id)), sidecar)),
+                })
+            }
+            Layout::Maildir => {
+                let name = format!("{}.{}", id, hostname());
+                Ok(Planned {
+                    write_to: self.dir.join("tmp").join(&name),
+                    deliver_to: self.dir.join("new").join(&name),
+                    sidecar: None,
+                })
+            }
+        }
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        match self.layout {
+            Layout::Flat => vec![self.dir.clone()],
+            Layout::Maildir => ["tmp", "new", "cur"].iter().map(|d| self.dir.join(d)).collect(),
+        }
+    }
+}
+
+/// Where one message goes. Maildir messages are written under `tmp/` and
+/// renamed into `new/` so readers never see partial files.
+struct Planned {
+    write_to: PathBuf,
+    deliver_to: PathBuf,
+    sidecar: Option<(PathBuf, Vec<u8>)>,
+}
+
+/// Envelope as stored in the JSON sidecar.
+#[derive(serde::Serialize)]
+struct EnvelopeRecord {
+    from: Option<String>,
+    to: Vec<String>,
+}
+
+impl From<&Envelope> for EnvelopeRecord {
+    fn from(envelope: &Envelope) -> Self {
+        Self {
+            from: envelope.from().map(|a| a.to_string()),
+            to: envelope.to().iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+/// Unique, roughly time ordered name for a new file.
+pub(crate) fn unique_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}.{}.{}", nanos, std::process::id(), n)
+}
+
+/// Host part of Maildir file names, with `/` and `:` replaced as the
+/// Maildir spec requires.
+fn hostname() -> String {
+    let name = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "localhost".into());
+    name.replace('/', "\\057").replace(':', "\\072")
+}
+
+impl Transport for FileTransport {
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        for dir in self.dirs() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let plan = self.plan(envelope)?;
+        if let Some((path, contents)) = &plan.sidecar {
+            std::fs::write(path, contents)?;
+        }
+        std::fs::write(&plan.write_to, message)?;
+        if plan.write_to != plan.deliver_to {
+            std::fs::rename(&plan.write_to, &plan.deliver_to)?;
+        }
+        Ok(format!("written to {}", plan.deliver_to.display()))
+    }
+}
+
+impl AsyncTransport for FileTransport {
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>> {
+        Box::pin(async move {
+            for dir in self.dirs() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            let plan = self.plan(envelope)?;
+            if let Some((path, contents)) = &plan.sidecar {
+                tokio::fs::write(path, contents).await?;
+            }
+            tokio::fs::write(&plan.write_to, message).await?;
+            if plan.write_to != plan.deliver_to {
+                tokio::fs::rename(&plan.write_to, &plan.deliver_to).await?;
+            }
+            Ok(format!("written to {}", plan.deliver_to.display()))
+        })
+    }
+}
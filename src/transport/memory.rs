@@ -0,0 +1,175 @@
+//! Transport that keeps every message in memory.
+
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+
+use super::{AsyncTransport, Envelope, Transport};
+use crate::email_sender::MailkitError;
+use crate::mime::Part;
+
+/// A message captured by [`InMemoryTransport`].
+///
+/// Besides the raw bytes it offers decoded views of the message for use in
+/// assertions.
+#[derive(Debug, Clone)]
+pub struct SentMessage {
+    envelope: Envelope,
+    raw: Vec<u8>,
+    parsed: Part,
+}
+
+impl SentMessage {
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    /// The message exactly as it would have been sent.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Envelope sender (`MAIL FROM`).
+    pub fn from(&self) -> Option<String> {
+        self.envelope.from().map(|a| a.to_string())
+    }
+
+    /// Envelope recipients (`RCPT TO`), including Cc and Bcc.
+    pub fn recipients(&self) -> Vec<String> {
+        self.envelope.to().iter().map(|a| a.to_string()).collect()
+    }
+
+    /// Whether `address` is one of the envelope recipients, compared
+    /// case-insensitively.
+    pub fn is_to(&self, address: &str) -> bool {
+        self.envelope
+            .to()
+            .iter()
+            .any(|a| a.to_string().eq_ignore_ascii_case(address.trim()))
+    }
+
+    /// Decoded `Subject` header.
+    pub fn subject(&self) -> Option<String> {
+        self.parsed.header("Subject")
+    }
+
+    /// First top-level header called `name` (case-insensitive), decoded.
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.parsed.header(name)
+    }
+
+    /// All top-level headers in message order, decoded.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.parsed.headers().to_vec()
+    }
+
+    /// Decoded `text/plain` body with line endings normalized to `\n`.
+    pub fn text(&self) -> Option<String> {
+        self.parsed.find_text("text/plain").map(normalize_newlines)
+    }
+
+    /// Decoded `text/html` body with line endings normalized to `\n`.
+    pub fn html(&self) -> Option<String> {
+        self.parsed.find_text("text/html").map(normalize_newlines)
+    }
+
+    /// Content-IDs of the inline parts, without angle brackets, in message
+    /// order.
+    pub fn inline_content_ids(&self) -> Vec<String> {
+        self.parsed
+            .walk()
+            .into_iter()
+            .filter(|p| !p.is_attachment())
+            .filter_map(|p| p.header("Content-ID"))
+            .map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+            .collect()
+    }
+
+    /// File names of all attachments in message order.
+    pub fn attachment_names(&self) -> Vec<String> {
+        self.parsed
+            .walk()
+            .into_iter()
+            .filter(|p| p.is_attachment())
+            .filter_map(|p| p.filename())
+            .collect()
+    }
+}
+
+fn normalize_newlines(s: String) -> String {
+    s.replace("\r\n", "\n")
+}
+
+/// Stores messages instead of delivering them.
+///
+/// Clones share the same storage, so keep a clone around to inspect what
+/// an [`EmailSender`](crate::EmailSender) sent.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTransport {
+    messages: Arc<Mutex<Vec<SentMessage>>>,
+}
+
+impl InMemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SentMessage>> {
+        self.messages.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// All captured messages in send order.
+    pub fn messages(&self) -> Vec<SentMessage> {
+        self.lock().clone()
+    }
+
+    /// Captured messages with `address` among the envelope recipients.
+    pub fn messages_to(&self, address: &str) -> Vec<SentMessage> {
+        self.lock().iter().filter(|m| m.is_to(address)).cloned().collect()
+    }
+
+    /// The most recently captured message.
+    pub fn last(&self) -> Option<SentMessage> {
+        self.lock().last().cloned()
+    }
+
+    /// Number of captured messages.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Forgets every captured message.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn capture(&self, envelope: &Envelope, message: &[u8]) -> String {
+        let mut messages = self.lock();
+        messages.push(SentMessage {
+            envelope: envelope.clone(),
+            raw: message.to_vec(),
+            parsed: Part::parse(message),
+        });
+        format!("captured message #{}", messages.len())
+    }
+}
+
+impl Transport for InMemoryTransport {
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        Ok(self.capture(envelope, message))
+    }
+}
+
+impl AsyncTransport for InMemoryTransport {
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>> {
+        Box::pin(async move { Ok(self.capture(envelope, message)) })
+    }
+}
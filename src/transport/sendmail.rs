@@ -0,0 +1,138 @@
+//! Transport that hands messages to a local `sendmail` binary.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use futures::future::BoxFuture;
+use tokio::io::AsyncWriteExt;
+
+use super::{AsyncTransport, Envelope, Transport};
+use crate::email_sender::MailkitError;
+
+pub(crate) const DEFAULT_SENDMAIL: &str = "/usr/sbin/sendmail";
+
+/// Pipes messages to `sendmail [args] -i -f <from> -- <recipients>`.
+///
+/// Recipients are always passed on the command line because `Bcc` is not
+/// part of the written message, so `-t` is not needed. A non-zero exit is
+/// reported as [`MailkitError::Sendmail`] with the captured stderr.
+///
+/// ```no_run
+/// use mailkit::transport::SendmailTransport;
+///
+/// let sendmail = SendmailTransport::with_command("/usr/lib/sendmail").arg("-oi");
+/// ```
+#[derive(Debug, Clone)]
+pub struct SendmailTransport {
+    command: PathBuf,
+    args: Vec<String>,
+}
+
+impl SendmailTransport {
+    /// Uses `/usr/sbin/sendmail`.
+    pub fn new() -> Self {
+        Self::with_command(DEFAULT_SENDMAIL)
+    }
+
+    /// Uses the binary at `command`.
+    pub fn with_command<P: Into<PathBuf>>(command: P) -> Self {
+        Self {
+            command: command.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Adds an argument passed before the envelope arguments.
+    pub fn arg<S: Into<String>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds several arguments passed before the envelope arguments.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn command(&self) -> &Path {
+        &self.command
+    }
+
+    fn command_args(&self, envelope: &Envelope) -> Vec<String> {
+        let mut args = self.args.clone();
+        args.push("-i".into());
+        if let Some(from) = envelope.from() {
+            args.push("-f".into());
+            args.push(from.to_string());
+        }
+        args.push("--".into());
+        args.extend(envelope.to().iter().map(|a| a.to_string()));
+        args
+    }
+}
+
+impl Default for SendmailTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A sendmail that exits early closes stdin; its exit status and stderr
+/// explain why better than the write error does.
+fn ignore_broken_pipe(result: std::io::Result<()>) -> std::io::Result<()> {
+    match result {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+fn check(output: Output) -> Result<String, MailkitError> {
+    if output.status.success() {
+        return Ok("accepted by sendmail".into());
+    }
+    Err(MailkitError::Sendmail {
+        status: output.status.code(),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+impl Transport for SendmailTransport {
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        let mut child = Command::new(&self.command)
+            .args(self.command_args(envelope))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            ignore_broken_pipe(stdin.write_all(message))?;
+        }
+        check(child.wait_with_output()?)
+    }
+}
+
+impl AsyncTransport for SendmailTransport {
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>> {
+        Box::pin(async move {
+            let mut child = tokio::process::Command::new(&self.command)
+                .args(self.command_args(envelope))
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                ignore_broken_pipe(stdin.write_all(message).await)?;
+            }
+            check(child.wait_with_output().await?)
+        })
+    }
+}
@@ -0,0 +1,350 @@
+//! Pooled SMTP delivery built on `lettre`'s SMTP client.
+
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use lettre::transport::smtp::authentication::{Credentials, DEFAULT_MECHANISMS};
+use lettre::transport::smtp::client::{AsyncSmtpConnection, SmtpConnection, TlsParameters};
+use lettre::transport::smtp::commands::{Data, Mail, Rcpt, Rset};
+use lettre::transport::smtp::extension::{ClientId, Extension, MailBodyParameter, MailParameter, ServerInfo};
+use lettre::transport::smtp::response::Response;
+
+use super::{AsyncTransport, Envelope, Transport};
+use crate::email_sender::MailkitError;
+use crate::pool::{ConnectionPool, PoolConfig, Pooled};
+
+/// How the SMTP connection is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    /// Implicit TLS from the first byte (SMTPS, usually port 465).
+    Wrapper,
+    /// Plaintext connection upgraded with `STARTTLS`; fails if the server
+    /// does not offer it.
+    StartTlsRequired,
+    /// Upgrade with `STARTTLS` when offered, otherwise stay on plaintext.
+    StartTlsOpportunistic,
+    /// Plaintext only. Meant for local relays and tools like Mailpit or
+    /// MailHog.
+    None,
+}
+
+impl std::str::FromStr for TlsMode {
+    type Err = MailkitError;
+
+    /// Parses the values accepted by `SMTP_TLS`: `wrapper` (or `tls`,
+    /// `smtps`), `starttls` (or `required`), `opportunistic` and `none`
+    /// (or `off`, `plain`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "wrapper" | "tls" | "smtps" => Ok(TlsMode::Wrapper),
+            "starttls" | "required" => Ok(TlsMode::StartTlsRequired),
+            "opportunistic" => Ok(TlsMode::StartTlsOpportunistic),
+            "none" | "off" | "plain" => Ok(TlsMode::None),
+            other => Err(MailkitError::Validation(format!("Invalid TLS mode: {}", other))),
+        }
+    }
+}
+
+/// Connection settings for the SMTP server, validated by
+/// [`EmailSenderBuilder`](crate::EmailSenderBuilder).
+pub(crate) struct SmtpSettings {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) credentials: Option<Credentials>,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) command_timeout: Duration,
+    pub(crate) hello_name: ClientId,
+    pub(crate) tls: TlsMode,
+    pub(crate) tls_parameters: Option<TlsParameters>,
+}
+
+/// SMTP transport keeping authenticated connections open between sends.
+///
+/// Created by [`EmailSenderBuilder`](crate::EmailSenderBuilder) from its
+/// host, credential, TLS and pool settings.
+pub struct SmtpTransport {
+    settings: SmtpSettings,
+    sync_pool: ConnectionPool<SmtpConnection>,
+    async_pool: ConnectionPool<AsyncSmtpConnection>,
+}
+
+impl SmtpTransport {
+    pub(crate) fn new(settings: SmtpSettings, pool: PoolConfig) -> Self {
+        Self {
+            settings,
+            sync_pool: ConnectionPool::new(pool.clone()),
+            async_pool: ConnectionPool::new(pool),
+        }
+    }
+
+    /// The configured TLS mode.
+    pub fn tls_mode(&self) -> TlsMode {
+        self.settings.tls
+    }
+
+    fn tls_parameters(&self) -> Result<&TlsParameters, MailkitError> {
+        self.settings
+            .tls_parameters
+            .as_ref()
+            .ok_or_else(|| MailkitError::Validation("TLS is not available for this sender".into()))
+    }
+
+    fn open_connection(&self, tls: TlsMode) -> Result<SmtpConnection, MailkitError> {
+        let smtp = &self.settings;
+        let wrapper = match tls {
+            TlsMode::Wrapper => Some(self.tls_parameters()?),
+            _ => None,
+        };
+
+        let mut conn = SmtpConnection::connect(
+            (smtp.host.as_str(), smtp.port),
+            Some(smtp.connect_timeout),
+            &smtp.hello_name,
+            wrapper,
+            None,
+        )?;
+        match tls {
+            TlsMode::StartTlsRequired => conn.starttls(self.tls_parameters()?, &smtp.hello_name)?,
+            TlsMode::StartTlsOpportunistic if conn.can_starttls() => {
+                conn.starttls(self.tls_parameters()?, &smtp.hello_name)?
+            }
+            _ => {}
+        }
+        conn.set_timeout(Some(smtp.command_timeout))?;
+        if let Some(creds) = &smtp.credentials {
+            conn.auth(DEFAULT_MECHANISMS, creds)?;
+        }
+        Ok(conn)
+    }
+
+    async fn open_connection_async(&self, tls: TlsMode) -> Result<AsyncSmtpConnection, MailkitError> {
+        let smtp = &self.settings;
+        let wrapper = match tls {
+            TlsMode::Wrapper => Some(self.tls_parameters()?.clone()),
+            _ => None,
+        };
+
+        let mut conn = AsyncSmtpConnection::connect_tokio1(
+            (smtp.host.as_str(), smtp.port),
+            Some(smtp.connect_timeout),
+            &smtp.hello_name,
+            wrapper,
+            None,
+        )
+        .await?;
+        let starttls = match tls {
+            TlsMode::StartTlsRequired => true,
+            TlsMode::StartTlsOpportunistic => conn.can_starttls(),
+            _ => false,
+        };
+        if starttls {
+            let params = self.tls_parameters()?.clone();
+            self.with_command_timeout(conn.starttls(params, &smtp.hello_name)).await??;
+        }
+        if let Some(creds) = &smtp.credentials {
+            self.with_command_timeout(conn.auth(DEFAULT_MECHANISMS, creds)).await??;
+        }
+        Ok(conn)
+    }
+
+    /// Reuses a live pooled connection or opens a new one.
+    fn checkout(&self, tls: TlsMode) -> Result<Pooled<SmtpConnection>, MailkitError> {
+        let (pooled, expired) = self.sync_pool.take(tls);
+        for mut conn in expired {
+            let _ = conn.quit();
+        }
+        if let Some(mut pooled) = pooled {
+            if pooled.conn.test_connected() {
+                return Ok(pooled);
+            }
+            pooled.conn.abort();
+        }
+        Ok(Pooled::new(self.open_connection(tls)?, tls))
+    }
+
+    async fn checkout_async(&self, tls: TlsMode) -> Result<Pooled<AsyncSmtpConnection>, MailkitError> {
+        let (pooled, expired) = self.async_pool.take(tls);
+        for mut conn in expired {
+            let _ = self.with_command_timeout(conn.quit()).await;
+        }
+        if let Some(mut pooled) = pooled
+            && let Ok(true) = self.with_command_timeout(pooled.conn.test_connected()).await
+        {
+            return Ok(pooled);
+        }
+        Ok(Pooled::new(self.open_connection_async(tls).await?, tls))
+    }
+
+    /// Bounds an async SMTP exchange by the configured command timeout.
+    async fn with_command_timeout<F: std::future::Future>(&self, fut: F) -> Result<F::Output, MailkitError> {
+        tokio::time::timeout(self.settings.command_timeout, fut)
+            .await
+            .map_err(|_| MailkitError::Io(std::io::Error::new(std::io::ErrorKind::TimedOut, "SMTP command timed out")))
+    }
+
+    /// Sends with an explicit TLS mode, used by the legacy `use_tls` flag.
+    pub(crate) fn send_with(&self, tls: TlsMode, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        let mut pooled = self.checkout(tls)?;
+
+        let response = match transaction(&mut pooled.conn, envelope, message) {
+            Ok(response) => response,
+            Err(err) => {
+                // a rejected transaction leaves the connection usable once
+                // reset; anything else gets it closed
+                if !rejected(&err) || pooled.conn.command(Rset).is_err() {
+                    pooled.conn.abort();
+                } else if let Some(mut conn) = self.sync_pool.put(pooled) {
+                    let _ = conn.quit();
+                }
+                return Err(err);
+            }
+        };
+        pooled.sent += 1;
+        if let Some(mut conn) = self.sync_pool.put(pooled) {
+            let _ = conn.quit();
+        }
+        Ok(describe(&response))
+    }
+
+    pub(crate) async fn send_with_async(
+        &self,
+        tls: TlsMode,
+        envelope: &Envelope,
+        message: &[u8],
+    ) -> Result<String, MailkitError> {
+        let mut pooled = self.checkout_async(tls).await?;
+
+        let sent = self.with_command_timeout(transaction_async(&mut pooled.conn, envelope, message));
+        let response = match sent.await {
+            Ok(Ok(response)) => response,
+            Ok(Err(err)) => {
+                let reset = rejected(&err)
+                    && matches!(self.with_command_timeout(pooled.conn.command(Rset)).await, Ok(Ok(_)));
+                if !reset {
+                    let _ = self.with_command_timeout(pooled.conn.abort()).await;
+                } else if let Some(mut conn) = self.async_pool.put(pooled) {
+                    let _ = self.with_command_timeout(conn.quit()).await;
+                }
+                return Err(err);
+            }
+            // the exchange stopped halfway, so the connection is out of sync
+            Err(timeout) => return Err(timeout),
+        };
+        pooled.sent += 1;
+        if let Some(mut conn) = self.async_pool.put(pooled) {
+            let _ = self.with_command_timeout(conn.quit()).await;
+        }
+        Ok(describe(&response))
+    }
+}
+
+/// Runs one mail transaction like `SmtpConnection::send`, which closes the
+/// connection on any error, but leaves it open when the server rejects the
+/// message so it can be reset and reused.
+fn transaction(conn: &mut SmtpConnection, envelope: &Envelope, message: &[u8]) -> Result<Response, MailkitError> {
+    let params = mail_parameters(conn.server_info(), envelope, message)?;
+    conn.command(Mail::new(envelope.from().cloned(), params))?;
+    for to in envelope.to() {
+        conn.command(Rcpt::new(to.clone(), vec![]))?;
+    }
+    conn.command(Data)?;
+    Ok(conn.message(message)?)
+}
+
+/// Async version of [`transaction`].
+async fn transaction_async(
+    conn: &mut AsyncSmtpConnection,
+    envelope: &Envelope,
+    message: &[u8],
+) -> Result<Response, MailkitError> {
+    let params = mail_parameters(conn.server_info(), envelope, message)?;
+    conn.command(Mail::new(envelope.from().cloned(), params)).await?;
+    for to in envelope.to() {
+        conn.command(Rcpt::new(to.clone(), vec![])).await?;
+    }
+    conn.command(Data).await?;
+    Ok(conn.message(message).await?)
+}
+
+/// `SMTPUTF8` and `8BITMIME` parameters for `MAIL FROM`, or an error if the
+/// server lacks an extension the message needs.
+fn mail_parameters(info: &ServerInfo, envelope: &Envelope, message: &[u8]) -> Result<Vec<MailParameter>, MailkitError> {
+    let mut params = Vec::new();
+    let addresses = envelope.from().into_iter().chain(envelope.to());
+    if addresses.map(|a| a.as_ref()).any(|a: &str| !a.is_ascii()) {
+        if !info.supports_feature(Extension::SmtpUtfEight) {
+            return Err(MailkitError::Validation(
+                "Envelope contains non-ASCII addresses but the server does not support SMTPUTF8".into(),
+            ));
+        }
+        params.push(MailParameter::SmtpUtfEight);
+    }
+    if !message.is_ascii() {
+        if !info.supports_feature(Extension::EightBitMime) {
+            return Err(MailkitError::Validation(
+                "Message contains non-ASCII bytes but the server does not support 8BITMIME".into(),
+            ));
+        }
+        params.push(MailParameter::Body(MailBodyParameter::EightBitMime));
+    }
+    Ok(params)
+}
+
+/// Whether the server answered with a negative reply, or the message was
+/// refused before anything was sent, so the connection is still in step.
+/// `421` means the server is closing the connection.
+fn rejected(err: &MailkitError) -> bool {
+    match err {
+        MailkitError::Smtp(e) => e.status().is_some_and(|code| u16::from(code) != 421),
+        MailkitError::Validation(_) => true,
+        _ => false,
+    }
+}
+
+/// Formats a reply as `250 2.0.0 Ok: queued`.
+fn describe(response: &Response) -> String {
+    let lines: Vec<&str> = response.message().collect();
+    format!("{} {}", u16::from(response.code()), lines.join(" "))
+}
+
+impl Transport for SmtpTransport {
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        self.send_with(self.settings.tls, envelope, message)
+    }
+
+    /// Closes every idle pooled connection. Idle async connections are
+    /// dropped without `QUIT`; use [`AsyncTransport::close_async`] inside a
+    /// runtime to close them cleanly.
+    fn close(&self) {
+        for mut conn in self.sync_pool.drain() {
+            let _ = conn.quit();
+        }
+        drop(self.async_pool.drain());
+    }
+}
+
+impl AsyncTransport for SmtpTransport {
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>> {
+        Box::pin(self.send_with_async(self.settings.tls, envelope, message))
+    }
+
+    fn close_async(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            // sync connections quit with blocking I/O, off the runtime
+            let sync_pool = self.sync_pool.drain();
+            let sync_quit = tokio::task::spawn_blocking(move || {
+                for mut conn in sync_pool {
+                    let _ = conn.quit();
+                }
+            });
+            for mut conn in self.async_pool.drain() {
+                let _ = self.with_command_timeout(conn.quit()).await;
+            }
+            let _ = sync_quit.await;
+        })
+    }
+}
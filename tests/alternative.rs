@@ -0,0 +1,142 @@
+mod common;
+
+use common::{builder, raw, sender, template_sender};
+use mailkit::html_text::html_to_text;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{Email, json};
+
+fn content_type(transport: &InMemoryTransport) -> String {
+    transport.last().unwrap().header("Content-Type").unwrap()
+}
+
+#[test]
+fn text_and_html_are_alternatives() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .subject("Both")
+        .text("Hello in text")
+        .html("<p>Hello in <b>HTML</b></p>")
+        .build()
+        .unwrap();
+    sender(transport.clone()).deliver(&email).unwrap();
+
+    assert!(content_type(&transport).starts_with("multipart/alternative"));
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.text().as_deref(), Some("Hello in text"));
+    assert_eq!(sent.html().as_deref(), Some("<p>Hello in <b>HTML</b></p>"));
+    // the preferred alternative comes last
+    let raw = raw(&sent);
+    assert!(raw.find("text/plain").unwrap() < raw.find("text/html").unwrap());
+}
+
+#[tokio::test]
+async fn alternatives_nest_inside_mixed() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .subject("Attached")
+        .html("<h1>Report</h1><p>See attached.</p>")
+        .text_from_html()
+        .attachment("tests/files/sample.txt")
+        .build()
+        .unwrap();
+    sender(transport.clone()).deliver_async(&email).await.unwrap();
+
+    assert!(content_type(&transport).starts_with("multipart/mixed"));
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.text().as_deref(), Some("Report\n======\n\nSee attached.\n"));
+    assert_eq!(sent.html().as_deref(), Some("<h1>Report</h1><p>See attached.</p>"));
+    assert_eq!(sent.attachment_names(), ["sample.txt"]);
+}
+
+#[test]
+fn html_only_gets_generated_text() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .html("<p>Only HTML, with a <a href=\"https://example.com/x\">link</a></p>")
+        .build()
+        .unwrap();
+    sender(transport.clone()).deliver(&email).unwrap();
+
+    assert!(content_type(&transport).starts_with("multipart/alternative"));
+    assert_eq!(
+        transport.last().unwrap().text().as_deref(),
+        Some("Only HTML, with a link (https://example.com/x)\n")
+    );
+}
+
+#[test]
+fn auto_text_can_be_disabled() {
+    let transport = InMemoryTransport::new();
+    let sender = builder(transport.clone())
+        .auto_text(false)
+        .build()
+        .unwrap();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .html("<p>Only HTML</p>")
+        .build()
+        .unwrap();
+    assert_eq!(email.text_alternative(), None);
+    sender.deliver(&email).unwrap();
+
+    assert!(content_type(&transport).starts_with("text/html"));
+    assert_eq!(transport.last().unwrap().text(), None);
+}
+
+#[test]
+fn text_width_is_configurable() {
+    let transport = InMemoryTransport::new();
+    let sender = builder(transport.clone())
+        .text_width(12)
+        .build()
+        .unwrap();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .html("<p>one two three four five</p>")
+        .build()
+        .unwrap();
+    sender.deliver(&email).unwrap();
+    assert_eq!(
+        transport.last().unwrap().text().as_deref(),
+        Some("one two\nthree four\nfive\n")
+    );
+}
+
+#[test]
+fn text_template_replaces_generated_text() {
+    let transport = InMemoryTransport::new();
+    let sender = template_sender(transport.clone());
+    let email = sender
+        .render_template_with_text("rcpt@example.com", "Hi", "merge.html", "test.txt", &json!({"name": "Ada", "product": "MailKit"}))
+        .unwrap()
+        .build()
+        .unwrap();
+    sender.deliver(&email).unwrap();
+
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.text().unwrap().trim(), "Hello Ada");
+    assert!(sent.html().unwrap().contains("Ada"));
+}
+
+#[test]
+fn explicit_text_wins_over_generated() {
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .text("Hand written")
+        .html("<p>Generated</p>")
+        .text_from_html()
+        .build()
+        .unwrap();
+    assert_eq!(email.text_alternative().as_deref(), Some("Hand written"));
+}
+
+#[test]
+fn converts_basic_html() {
+    let html = "<html><head><title>T</title><style>p { color: red }</style></head>\
+                <body><p>Fish &amp; chips<br>for&nbsp;two</p><!-- note > here --><script>x()</script>\
+                <div>Caf&#233; &#x263A;</div></body></html>";
+    assert_eq!(html_to_text(html), "Fish & chips\nfor two\n\nCafé ☺\n");
+}
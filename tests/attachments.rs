@@ -0,0 +1,105 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::{raw, sender};
+use mailkit::attachment::Disposition;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{AttachmentSource, Email, MailkitError};
+
+#[test]
+fn attaches_bytes_and_readers() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .text("see attached")
+        .attachment("tests/files/sample.txt")
+        .attachment(AttachmentSource::bytes(b"%PDF-1.4".to_vec(), "invoice.pdf"))
+        .attachment(AttachmentSource::from_reader(Cursor::new("id,total\n1,9.99\n"), "export.csv").unwrap())
+        .build()
+        .unwrap();
+    sender(transport.clone()).deliver(&email).unwrap();
+
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.attachment_names(), ["sample.txt", "invoice.pdf", "export.csv"]);
+    let raw = raw(&transport.last().unwrap());
+    assert!(raw.contains("Content-Type: application/pdf"));
+    assert!(raw.contains("Content-Type: text/csv"));
+    assert!(raw.contains("id,total"));
+}
+
+#[tokio::test]
+async fn reads_async_readers() {
+    let transport = InMemoryTransport::new();
+    let file = tokio::fs::File::open("tests/files/sample.html").await.unwrap();
+    let source = AttachmentSource::from_async_reader(file, "page.html").await.unwrap();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .text("see attached")
+        .attachment(source)
+        .build()
+        .unwrap();
+    sender(transport.clone()).deliver_async(&email).await.unwrap();
+
+    assert_eq!(transport.last().unwrap().attachment_names(), ["page.html"]);
+    assert!(raw(&transport.last().unwrap()).contains("Content-Type: text/html"));
+}
+
+#[test]
+fn overrides_name_type_and_disposition() {
+    let transport = InMemoryTransport::new();
+    let source = AttachmentSource::path("tests/files/sample.txt")
+        .filename("notes.md")
+        .content_type("text/markdown; charset=utf-8")
+        .disposition(Disposition::Inline);
+    assert_eq!(source.name(), "notes.md");
+    assert_eq!(source.file_path(), Some("tests/files/sample.txt"));
+    assert!(source.is_inline());
+
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .text("body")
+        .attachment(source)
+        .build()
+        .unwrap();
+    sender(transport.clone()).deliver(&email).unwrap();
+
+    let raw = raw(&transport.last().unwrap());
+    assert!(raw.contains("Content-Disposition: inline; filename=\"notes.md\""));
+    assert!(raw.contains("Content-Type: text/markdown; charset=utf-8"));
+    // inline parts are not listed as attachments
+    assert!(transport.last().unwrap().attachment_names().is_empty());
+}
+
+#[test]
+fn bulk_copies_share_in_memory_attachments() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("placeholder@example.com")
+        .text("report")
+        .attachment(AttachmentSource::from_reader(&b"a,b\n"[..], "report.csv").unwrap())
+        .build()
+        .unwrap();
+    let recipients = vec!["a@example.com".to_string(), "b@example.com".to_string()];
+    let report = sender(transport.clone()).deliver_bulk(&email, &recipients, mailkit::BulkMode::ContinueOnError);
+
+    assert_eq!(report.sent(), 2);
+    for message in transport.messages() {
+        assert_eq!(message.attachment_names(), ["report.csv"]);
+    }
+}
+
+#[test]
+fn invalid_overrides_are_rejected() {
+    let bad_type = Email::builder()
+        .to("rcpt@example.com")
+        .attachment(AttachmentSource::bytes(vec![1, 2, 3], "data.bin").content_type("not a type"))
+        .build();
+    assert!(matches!(bad_type, Err(MailkitError::Validation(_))));
+
+    let no_name = Email::builder()
+        .to("rcpt@example.com")
+        .attachment(AttachmentSource::bytes(vec![1, 2, 3], " "))
+        .build();
+    assert!(matches!(no_name, Err(MailkitError::Validation(_))));
+}
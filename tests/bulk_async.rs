@@ -1,23 +1,119 @@
-use futures::stream::{FuturesUnordered, StreamExt};
-use lettre::message::{Message, Mailbox};
-use lettre::transport::stub::AsyncStubTransport;
-use lettre::AsyncTransport;
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{StubTransport, builder, email, sender};
+use mailkit::bulk::Outcome;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{BulkMode, Email};
+
+fn recipients() -> Vec<String> {
+    vec![
+        "rcpt0@example.com".into(),
+        "not-an-address".into(),
+        "rcpt2@example.com".into(),
+    ]
+}
 
 #[tokio::test]
 async fn bulk_async_futures_run() {
-    let mailer = AsyncStubTransport::new_ok();
-    let mut futs = FuturesUnordered::new();
-    for i in 0..5 {
-        let msg = Message::builder()
-            .from("sender@example.com".parse::<Mailbox>().unwrap())
-            .to("rcpt@example.com".parse::<Mailbox>().unwrap())
-            .subject("test")
-            .body(format!("body {}", i))
-            .unwrap();
-        futs.push(mailer.send(msg));
-    }
-
-    while let Some(res) = futs.next().await {
-        res.expect("send failed");
-    }
+    let mailer = InMemoryTransport::new();
+    let sender = sender(mailer.clone());
+
+    let recipients: Vec<String> = (0..5).map(|i| format!("rcpt{}@example.com", i)).collect();
+    let report = sender
+        .send_bulk_async(recipients.clone(), "test", "body", None, None, None, false, false)
+        .await;
+
+    assert!(report.is_success());
+    assert_eq!(report.sent(), 5);
+    assert_eq!(mailer.messages().len(), 5);
+    let order: Vec<&str> = report.results().iter().map(|r| r.recipient()).collect();
+    assert_eq!(order, recipients);
+}
+
+#[test]
+fn bulk_continues_after_failure() {
+    let mailer = InMemoryTransport::new();
+    let sender = sender(mailer.clone());
+
+    let report = sender.send_bulk(recipients(), "test", "body", None, None, None, false, false);
+
+    assert_eq!((report.total(), report.sent(), report.failed(), report.skipped()), (3, 2, 1, 0));
+    let failures: Vec<&str> = report.failures().map(|(rcpt, _)| rcpt).collect();
+    assert_eq!(failures, ["not-an-address"]);
+    assert_eq!(mailer.messages_to("rcpt2@example.com").len(), 1);
+
+    let delivery = report.results()[0].delivery().unwrap();
+    assert!(delivery.message_id().starts_with('<') && delivery.message_id().ends_with("@example.com>"));
+    assert_eq!(delivery.response(), "captured message #1");
+    let sent = mailer.messages_to("rcpt0@example.com").remove(0);
+    assert_eq!(sent.header("Message-ID").as_deref(), Some(delivery.message_id()));
+}
+
+#[tokio::test]
+async fn bulk_can_stop_on_first_error() {
+    let mailer = InMemoryTransport::new();
+    let sender = sender(mailer.clone());
+    let email = Email::builder()
+        .to("placeholder@example.com")
+        .subject("test")
+        .text("body")
+        .build()
+        .unwrap();
+
+    let report = sender.deliver_bulk(&email, &recipients(), BulkMode::StopOnFirstError);
+    assert_eq!((report.sent(), report.failed(), report.skipped()), (1, 1, 1));
+    assert!(matches!(report.results()[2].outcome(), Outcome::Skipped));
+    assert_eq!(mailer.len(), 1);
+
+    let report = sender
+        .deliver_bulk_async(&email, &recipients(), BulkMode::ContinueOnError)
+        .await;
+    assert_eq!((report.sent(), report.failed()), (2, 1));
+    assert!(mailer.messages_to("placeholder@example.com").is_empty());
+}
+
+#[tokio::test]
+async fn stop_on_first_error_finishes_sends_in_flight() {
+    let transport = Arc::new(
+        StubTransport::new()
+            .delay(Duration::from_millis(50))
+            .fail_to("fail@example.com"),
+    );
+    let sender = builder(transport.clone()).max_in_flight(2).build().unwrap();
+    let recipients: Vec<String> = ["slow@example.com", "fail@example.com", "next@example.com", "last@example.com"]
+        .map(String::from)
+        .into();
+
+    // the slow send is still running when the second one fails
+    let report = sender
+        .deliver_bulk_async(&email("test"), &recipients, BulkMode::StopOnFirstError)
+        .await;
+
+    let rows: Vec<(&str, &str)> = report
+        .results()
+        .iter()
+        .map(|r| {
+            let outcome = match r.outcome() {
+                Outcome::Sent(_) => "sent",
+                Outcome::Failed(_) => "failed",
+                Outcome::RenderFailed(_) => "render failed",
+                Outcome::Skipped => "skipped",
+            };
+            (r.recipient(), outcome)
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("slow@example.com", "sent"),
+            ("fail@example.com", "failed"),
+            ("next@example.com", "skipped"),
+            ("last@example.com", "skipped"),
+        ]
+    );
+    assert_eq!(transport.attempted(), ["slow@example.com", "fail@example.com"]);
+    assert_eq!(transport.mailer().messages_to("slow@example.com").len(), 1);
 }
@@ -0,0 +1,110 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use common::{StubTransport, builder, email, sender, template_sender};
+use futures::{SinkExt, StreamExt, stream};
+use mailkit::{CancelToken, json};
+use mailkit::recipients::Recipient;
+use mailkit::transport::InMemoryTransport;
+
+#[tokio::test]
+async fn streams_template_results() {
+    let mailer = InMemoryTransport::new();
+    let sender = template_sender(mailer.clone());
+
+    let recipients = stream::iter(vec![
+        Recipient::new("ada@example.com", json!({"name": "Ada"})),
+        Recipient::new("bad-address", json!({"name": "Bad"})),
+        Recipient::new("alan@example.com", json!({"name": "Alan"})),
+    ]);
+    let base = json!({"product": "MailKit"});
+    let results: Vec<_> = sender
+        .send_template_stream(recipients, "Hi {{ name }}", "merge.html", &base, CancelToken::new())
+        .collect()
+        .await;
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results.iter().filter(|r| r.delivery().is_some()).count(), 2);
+    let failed: Vec<&str> = results.iter().filter(|r| r.error().is_some()).map(|r| r.recipient()).collect();
+    assert_eq!(failed, ["bad-address"]);
+    assert_eq!(
+        mailer.messages_to("alan@example.com")[0].subject().as_deref(),
+        Some("Hi Alan")
+    );
+}
+
+#[tokio::test]
+async fn pulls_recipients_with_backpressure() {
+    let transport = Arc::new(StubTransport::new().delay(Duration::from_millis(20)));
+    let sender = builder(transport.clone())
+        .max_in_flight(2)
+        .build()
+        .unwrap();
+
+    let pulled = Arc::new(AtomicUsize::new(0));
+    let counter = pulled.clone();
+    let recipients = stream::iter(0..100).map(move |i| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        format!("rcpt{}@example.com", i)
+    });
+
+    let email = email("stream");
+    let mut results = Box::pin(sender.deliver_stream(&email, recipients, CancelToken::new()));
+    results.next().await.unwrap();
+    assert!(pulled.load(Ordering::SeqCst) <= 3);
+}
+
+#[tokio::test]
+async fn cancel_stops_taking_recipients() {
+    let mailer = InMemoryTransport::new();
+    let sender = sender(mailer.clone());
+
+    let (mut tx, rx) = futures::channel::mpsc::channel::<String>(8);
+    let cancel = CancelToken::new();
+    let email = email("stream");
+    let mut results = Box::pin(sender.deliver_stream(&email, rx, cancel.clone()));
+
+    for i in 0..3 {
+        tx.send(format!("rcpt{}@example.com", i)).await.unwrap();
+        assert!(results.next().await.unwrap().delivery().is_some());
+    }
+    cancel.cancel();
+    let _ = tx.send("late@example.com".into()).await;
+
+    assert!(results.next().await.is_none());
+    assert_eq!(mailer.len(), 3);
+    assert!(mailer.messages_to("late@example.com").is_empty());
+}
+
+#[tokio::test]
+async fn cancelling_on_failure_finishes_sends_in_flight() {
+    let transport = Arc::new(
+        StubTransport::new()
+            .delay(Duration::from_millis(50))
+            .fail_to("fail@example.com"),
+    );
+    let sender = builder(transport.clone()).max_in_flight(2).build().unwrap();
+    let recipients = stream::iter(["slow@example.com", "fail@example.com", "next@example.com", "last@example.com"]);
+
+    let cancel = CancelToken::new();
+    let email = email("stream");
+    let mut results = Box::pin(sender.deliver_stream(&email, recipients, cancel.clone()));
+    let mut rows = Vec::new();
+    while let Some(result) = results.next().await {
+        if result.error().is_some() {
+            cancel.cancel();
+        }
+        rows.push((result.recipient().to_string(), result.delivery().is_some()));
+    }
+
+    // the failure arrives first; the slow send it overlapped still completes
+    assert_eq!(
+        rows,
+        [("fail@example.com".to_string(), false), ("slow@example.com".to_string(), true)]
+    );
+    assert_eq!(transport.attempted(), ["slow@example.com", "fail@example.com"]);
+    assert_eq!(transport.mailer().len(), 1);
+}
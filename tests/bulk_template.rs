@@ -0,0 +1,116 @@
+mod common;
+
+use common::{builder, template_sender};
+use mailkit::bulk::Outcome;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{BulkMode, JsonValue, json};
+
+fn recipients() -> Vec<(String, JsonValue)> {
+    vec![
+        ("ada@example.com".into(), json!({"name": "Ada"})),
+        ("nobody@example.com".into(), json!({})),
+        ("alan@example.com".into(), json!({"name": "Alan", "product": "Tera"})),
+    ]
+}
+
+#[test]
+fn renders_each_recipient() {
+    let mailer = InMemoryTransport::new();
+    let sender = template_sender(mailer.clone());
+
+    let report = sender.send_bulk_template(
+        recipients(),
+        "Welcome, {{ name }}!",
+        "merge.html",
+        &json!({"product": "MailKit"}),
+        BulkMode::ContinueOnError,
+    );
+
+    assert_eq!((report.sent(), report.render_failed(), report.failed()), (2, 1, 0));
+    assert!(matches!(report.results()[1].outcome(), Outcome::RenderFailed(_)));
+
+    let ada = mailer.messages_to("ada@example.com").remove(0);
+    assert_eq!(ada.subject().as_deref(), Some("Welcome, Ada!"));
+    assert_eq!(ada.html().unwrap().trim(), "<p>Hi Ada, welcome to MailKit.</p>");
+
+    let alan = mailer.messages_to("alan@example.com").remove(0);
+    assert_eq!(alan.html().unwrap().trim(), "<p>Hi Alan, welcome to Tera.</p>");
+}
+
+#[tokio::test]
+async fn async_reports_render_and_delivery_errors() {
+    let mailer = InMemoryTransport::new();
+    let sender = template_sender(mailer.clone());
+    let mut recipients = recipients();
+    recipients.push(("broken-address".into(), json!({"name": "X"})));
+
+    let report = sender
+        .send_bulk_template_async(
+            recipients,
+            "Hi {{ name }}",
+            "merge.html",
+            &json!({"product": "MailKit"}),
+            BulkMode::ContinueOnError,
+        )
+        .await;
+
+    assert_eq!((report.sent(), report.render_failed(), report.failed()), (2, 1, 1));
+    assert!(matches!(report.results()[3].outcome(), Outcome::Failed(_)));
+    assert_eq!(mailer.len(), 2);
+}
+
+#[test]
+fn subjects_use_the_senders_filters() {
+    let mut tera = tera::Tera::new("tests/templates/**/*").unwrap();
+    tera.register_filter("shout", |value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>| {
+        Ok(tera::Value::String(value.as_str().unwrap_or_default().to_uppercase()))
+    });
+    let mailer = InMemoryTransport::new();
+    let sender = builder(mailer.clone()).tera(tera).build().unwrap();
+
+    let report = sender.send_bulk_template(
+        vec![("ada@example.com".to_string(), json!({"name": "Ada"}))],
+        "Welcome, {{ name | shout }}!",
+        "merge.html",
+        &json!({"product": "MailKit"}),
+        BulkMode::ContinueOnError,
+    );
+
+    assert!(report.is_success());
+    assert_eq!(mailer.last().unwrap().subject().as_deref(), Some("Welcome, ADA!"));
+}
+
+#[tokio::test]
+async fn merge_uses_front_matter_and_companions() {
+    let mailer = InMemoryTransport::new();
+    let sender = template_sender(mailer.clone());
+    let recipients = vec![("ada@example.com".to_string(), json!({"name": "Ada"}))];
+
+    let report = sender
+        .send_bulk_template_async(recipients.clone(), "unused", "welcome.html", &json!({}), BulkMode::ContinueOnError)
+        .await;
+    assert!(report.is_success());
+    let sent = mailer.last().unwrap();
+    assert_eq!(sent.subject().as_deref(), Some("Welcome, Ada!"));
+    assert_eq!(sent.text().as_deref(), Some("Hi Ada, thanks for signing up.\n"));
+    assert!(sent.html().unwrap().starts_with("<h1>Hi Ada</h1>"));
+
+    sender.send_bulk_template(recipients, "unused", "receipt.html", &json!({"order": "42"}), BulkMode::ContinueOnError);
+    assert_eq!(mailer.last().unwrap().subject().as_deref(), Some("Your receipt for order 42"));
+}
+
+#[test]
+fn invalid_subject_template_fails_every_recipient() {
+    let mailer = InMemoryTransport::new();
+    let sender = template_sender(mailer.clone());
+
+    let report = sender.send_bulk_template(
+        recipients(),
+        "Hi {{ name",
+        "merge.html",
+        &json!({"product": "MailKit"}),
+        BulkMode::ContinueOnError,
+    );
+    assert_eq!(report.render_failed(), 3);
+    assert!(mailer.is_empty());
+}
@@ -0,0 +1,62 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code, unused_imports)]
+
+mod smtp;
+mod stub;
+
+use std::env;
+use std::ffi::OsStr;
+
+use mailkit::transport::memory::SentMessage;
+use mailkit::transport::{AsyncTransport, Transport};
+use mailkit::{Email, EmailSender, EmailSenderBuilder, TlsMode};
+
+pub use smtp::{MockSmtp, Received};
+pub use stub::StubTransport;
+
+/// A builder sending as `sender@example.com` through `transport`.
+pub fn builder<T: Transport + AsyncTransport + 'static>(transport: T) -> EmailSenderBuilder {
+    EmailSender::builder().from("sender@example.com").transport(transport)
+}
+
+pub fn sender<T: Transport + AsyncTransport + 'static>(transport: T) -> EmailSender {
+    builder(transport).build().unwrap()
+}
+
+/// Like [`sender`], with the templates in `tests/templates` loaded.
+pub fn template_sender<T: Transport + AsyncTransport + 'static>(transport: T) -> EmailSender {
+    builder(transport).template_dir("tests/templates").build().unwrap()
+}
+
+/// A builder for a plaintext connection to a [`MockSmtp`] on `port`.
+pub fn smtp_builder(port: u16) -> EmailSenderBuilder {
+    EmailSender::builder()
+        .from("sender@example.com")
+        .host("127.0.0.1")
+        .port(port)
+        .tls(TlsMode::None)
+}
+
+/// A plain-text email to `rcpt@example.com`.
+pub fn email(subject: &str) -> Email {
+    Email::builder()
+        .to("rcpt@example.com")
+        .subject(subject)
+        .text("body")
+        .build()
+        .unwrap()
+}
+
+/// The raw message as text, for asserting on MIME structure.
+pub fn raw(message: &SentMessage) -> String {
+    String::from_utf8_lossy(message.raw()).into_owned()
+}
+
+pub fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(key: K, val: V) {
+    unsafe { env::set_var(key, val) }
+}
+
+pub fn remove_var<K: AsRef<OsStr>>(key: K) {
+    unsafe { env::remove_var(key) }
+}
@@ -0,0 +1,129 @@
+//! A tiny in-process SMTP server.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A message accepted by [`MockSmtp`].
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub from: String,
+    pub rcpt: Vec<String>,
+    pub data: String,
+}
+
+#[derive(Default)]
+struct State {
+    messages: Mutex<Vec<Received>>,
+    data_replies: Mutex<VecDeque<String>>,
+    connections: AtomicUsize,
+    data_attempts: AtomicUsize,
+}
+
+/// Plaintext SMTP server listening on `127.0.0.1`.
+pub struct MockSmtp {
+    pub port: u16,
+    state: Arc<State>,
+}
+
+impl MockSmtp {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(State::default());
+        let shared = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let state = shared.clone();
+                state.connections.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || handle(stream, state));
+            }
+        });
+        Self { port, state }
+    }
+
+    /// Queues a reply for the end of the next `DATA` command instead of
+    /// `250`, e.g. `"451 4.3.0 Try again later"`.
+    pub fn reply_to_data(&self, reply: &str) {
+        self.state.data_replies.lock().unwrap().push_back(reply.to_string());
+    }
+
+    pub fn messages(&self) -> Vec<Received> {
+        self.state.messages.lock().unwrap().clone()
+    }
+
+    /// Number of completed `DATA` commands, accepted or not.
+    pub fn data_attempts(&self) -> usize {
+        self.state.data_attempts.load(Ordering::SeqCst)
+    }
+
+    /// Number of TCP connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+}
+
+fn handle(stream: TcpStream, state: Arc<State>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut out = stream;
+    let reply = |out: &mut TcpStream, line: &str| {
+        let _ = out.write_all(format!("{}\r\n", line).as_bytes());
+    };
+
+    reply(&mut out, "220 mock ESMTP ready");
+    let mut current = Received { from: String::new(), rcpt: Vec::new(), data: String::new() };
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let cmd = line.trim_end().to_string();
+        let upper = cmd.to_ascii_uppercase();
+        if upper.starts_with("EHLO") || upper.starts_with("HELO") {
+            reply(&mut out, "250-mock");
+            reply(&mut out, "250-8BITMIME");
+            reply(&mut out, "250-SMTPUTF8");
+            reply(&mut out, "250 AUTH PLAIN LOGIN");
+        } else if upper.starts_with("AUTH") {
+            reply(&mut out, "235 2.7.0 Authentication successful");
+        } else if upper.starts_with("MAIL FROM:") {
+            current = Received { from: cmd[10..].trim_matches(|c| c == '<' || c == '>' || c == ' ').to_string(), rcpt: Vec::new(), data: String::new() };
+            reply(&mut out, "250 2.1.0 Ok");
+        } else if upper.starts_with("RCPT TO:") {
+            current.rcpt.push(cmd[8..].trim_matches(|c| c == '<' || c == '>' || c == ' ').to_string());
+            reply(&mut out, "250 2.1.5 Ok");
+        } else if upper == "DATA" {
+            reply(&mut out, "354 End data with <CR><LF>.<CR><LF>");
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
+                }
+                if line == ".\r\n" {
+                    break;
+                }
+                current.data.push_str(&line);
+            }
+            state.data_attempts.fetch_add(1, Ordering::SeqCst);
+            match state.data_replies.lock().unwrap().pop_front() {
+                Some(custom) => reply(&mut out, &custom),
+                None => {
+                    state.messages.lock().unwrap().push(current.clone());
+                    reply(&mut out, "250 2.0.0 Ok: queued");
+                }
+            }
+        } else if upper == "RSET" || upper == "NOOP" {
+            reply(&mut out, "250 2.0.0 Ok");
+        } else if upper == "QUIT" {
+            reply(&mut out, "221 2.0.0 Bye");
+            return;
+        } else {
+            reply(&mut out, "502 5.5.2 Command not recognized");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! A configurable stand-in transport.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use mailkit::MailkitError;
+use mailkit::transport::{AsyncTransport, Envelope, InMemoryTransport, Transport};
+
+/// Records messages like `InMemoryTransport`, optionally failing or
+/// slowing down sends.
+///
+/// Failures are connection errors, which count as transient. Wrap the stub
+/// in an `Arc` to inspect it after handing it to a sender.
+#[derive(Default)]
+pub struct StubTransport {
+    mailer: InMemoryTransport,
+    fail_first: usize,
+    fail_to: Vec<String>,
+    delay: Duration,
+    calls: AtomicUsize,
+    current: AtomicUsize,
+    peak: AtomicUsize,
+    order: Mutex<Vec<String>>,
+}
+
+impl StubTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the first `n` sends.
+    pub fn fail_first(mut self, n: usize) -> Self {
+        self.fail_first = n;
+        self
+    }
+
+    /// Fails every send to `address` at once, without the delay.
+    pub fn fail_to(mut self, address: &str) -> Self {
+        self.fail_to.push(address.to_string());
+        self
+    }
+
+    /// Holds every other send for `delay` before accepting it.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// The messages accepted so far.
+    pub fn mailer(&self) -> &InMemoryTransport {
+        &self.mailer
+    }
+
+    /// Number of sends attempted, failed ones included.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Most sends that were running at the same time.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    /// Envelope recipients of every attempted send, in the order the sends
+    /// started.
+    pub fn attempted(&self) -> Vec<String> {
+        self.order.lock().unwrap().clone()
+    }
+
+    /// Records the attempt and returns the error to fail it with, if any.
+    fn start(&self, envelope: &Envelope) -> Option<MailkitError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        let to: Vec<String> = envelope.to().iter().map(|a| a.to_string()).collect();
+        let fail = call < self.fail_first || to.iter().any(|a| self.fail_to.contains(a));
+        self.order.lock().unwrap().extend(to);
+        fail.then(|| std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into())
+    }
+
+    fn enter(&self) {
+        let now = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(now, Ordering::SeqCst);
+    }
+
+    fn leave(&self) {
+        self.current.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Transport for StubTransport {
+    fn send_raw(&self, envelope: &Envelope, message: &[u8]) -> Result<String, MailkitError> {
+        if let Some(err) = self.start(envelope) {
+            return Err(err);
+        }
+        self.enter();
+        std::thread::sleep(self.delay);
+        self.leave();
+        self.mailer.send_raw(envelope, message)
+    }
+}
+
+impl AsyncTransport for StubTransport {
+    fn send_raw_async<'a>(
+        &'a self,
+        envelope: &'a Envelope,
+        message: &'a [u8],
+    ) -> BoxFuture<'a, Result<String, MailkitError>> {
+        Box::pin(async move {
+            if let Some(err) = self.start(envelope) {
+                return Err(err);
+            }
+            self.enter();
+            tokio::time::sleep(self.delay).await;
+            self.leave();
+            self.mailer.send_raw(envelope, message)
+        })
+    }
+}
@@ -1,15 +1,9 @@
+mod common;
+
+use common::{remove_var, set_var};
 use mailkit::EmailSender;
 use serial_test::serial;
 use std::env;
-use std::ffi::OsStr;
-
-fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(key: K, val: V) {
-    unsafe { env::set_var(key, val) }
-}
-
-fn remove_var<K: AsRef<OsStr>>(key: K) {
-    unsafe { env::remove_var(key) }
-}
 
 #[test]
 #[serial]
@@ -65,6 +59,31 @@ fn from_env_valid() {
     assert!(res.is_ok());
 }
 
+#[test]
+#[serial]
+fn from_env_file_transport() {
+    let dir = env::temp_dir().join(format!("mailkit-env-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    set_var("MAILKIT_TEMPLATE_DIR", "tests/templates");
+    set_var("EMAIL", "user@example.com");
+    remove_var("SMTP_SERVER");
+    remove_var("EMAIL_PASSWORD");
+    set_var("MAILKIT_TRANSPORT", format!("file:{}", dir.display()));
+
+    let res = EmailSender::from_env();
+    remove_var("MAILKIT_TRANSPORT");
+    let sender = res.unwrap();
+    let email = mailkit::Email::builder()
+        .to("rcpt@example.com")
+        .subject("env")
+        .text("hi")
+        .build()
+        .unwrap();
+    sender.deliver(&email).unwrap();
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
 #[test]
 #[serial]
 fn attach_txt_content_type() {
@@ -151,3 +170,83 @@ fn send_real_gmail() {
 
     assert!(result.is_ok(), "Failed to send email: {:?}", result.err());
 }
+
+#[test]
+fn email_builder_requires_recipient() {
+    use mailkit::Email;
+    let res = Email::builder().subject("Hi").text("body").build();
+    assert!(res.is_err());
+}
+
+#[test]
+#[serial]
+fn build_message_from_email() {
+    use mailkit::Email;
+    set_var("MAILKIT_TEMPLATE_DIR", "tests/templates");
+    let sender = EmailSender::new(
+        "user@example.com",
+        "smtp.example.com",
+        "password",
+        25,
+        1,
+        true,
+    )
+    .unwrap();
+
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .cc("copy@example.com")
+        .reply_to("support@example.com")
+        .subject("Greetings")
+        .text("Hello there")
+        .header("X-Campaign", "spring")
+        .attachment("tests/files/sample.txt")
+        .build()
+        .unwrap();
+    assert_eq!(email.to(), ["rcpt@example.com".to_string()]);
+    assert_eq!(email.attachments().len(), 1);
+
+    let msg = sender.build_message(&email).unwrap();
+    let raw = String::from_utf8(msg.formatted()).unwrap();
+    assert!(raw.contains("Subject: Greetings"));
+    assert!(raw.contains("Reply-To: support@example.com"));
+    assert!(raw.contains("X-Campaign: spring"));
+    assert!(raw.contains("Content-Type: multipart/mixed"));
+}
+
+#[test]
+fn builder_valid() {
+    use std::time::Duration;
+    let sender = EmailSender::builder()
+        .from("noreply@example.com")
+        .host("smtp.example.com")
+        .port(2525)
+        .username("apikey")
+        .password("secret")
+        .connect_timeout(Duration::from_secs(3))
+        .command_timeout(Duration::from_secs(20))
+        .helo_name("mailer.example.com")
+        .template_dir("tests/templates")
+        .build();
+    assert!(sender.is_ok());
+}
+
+#[test]
+fn builder_reports_all_problems() {
+    let err = EmailSender::builder()
+        .from("invalid")
+        .port(0)
+        .username("apikey")
+        .build()
+        .err()
+        .expect("builder should fail");
+    match err {
+        mailkit::MailkitError::Validation(msg) => {
+            assert!(msg.contains("invalid from address"));
+            assert!(msg.contains("SMTP host is not set"));
+            assert!(msg.contains("port must not be 0"));
+            assert!(msg.contains("username is set without a password"));
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{MockSmtp, email, smtp_builder};
+use mailkit::{ErrorKind, MailkitError};
+
+fn rejected(reply: &str) -> MailkitError {
+    let server = MockSmtp::start();
+    server.reply_to_data(reply);
+    let sender = smtp_builder(server.port).build().unwrap();
+    sender.deliver(&email("errors")).unwrap_err()
+}
+
+#[test]
+fn mailbox_full_is_transient() {
+    let err = rejected("452 4.2.2 Mailbox full");
+    assert_eq!(err.kind(), ErrorKind::Smtp);
+    assert!(err.is_transient());
+    assert!(!err.is_permanent());
+    assert_eq!(err.smtp_code(), Some(452));
+    assert_eq!(err.enhanced_status(), Some((4, 2, 2)));
+    assert_eq!(err.response_text().as_deref(), Some("4.2.2 Mailbox full"));
+}
+
+#[test]
+fn unknown_user_is_permanent() {
+    let err = rejected("550 5.1.1 No such user");
+    assert!(err.is_permanent());
+    assert!(!err.is_transient());
+    assert_eq!(err.smtp_code(), Some(550));
+    assert_eq!(err.enhanced_status(), Some((5, 1, 1)));
+}
+
+#[test]
+fn reply_without_enhanced_status() {
+    let err = rejected("554 Transaction failed");
+    assert_eq!(err.smtp_code(), Some(554));
+    assert_eq!(err.enhanced_status(), None);
+    assert_eq!(err.response_text().as_deref(), Some("Transaction failed"));
+}
+
+#[test]
+fn kinds_of_other_variants() {
+    let io = MailkitError::Io(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+    assert_eq!(io.kind().as_str(), "io");
+    assert!(io.is_transient());
+    assert_eq!(io.smtp_code(), None);
+
+    let validation = MailkitError::Validation("bad".into());
+    assert_eq!(validation.kind(), ErrorKind::Validation);
+    assert!(validation.is_permanent());
+
+    let missing = MailkitError::MissingEnvVar("EMAIL");
+    assert_eq!(missing.kind().to_string(), "missing_env_var");
+
+    let tempfail = MailkitError::Sendmail { status: Some(75), stderr: String::new() };
+    assert!(tempfail.is_transient());
+    let unavailable = MailkitError::Sendmail { status: Some(69), stderr: String::new() };
+    assert!(unavailable.is_permanent());
+    assert_eq!(unavailable.kind().as_str(), "sendmail");
+}
@@ -0,0 +1,76 @@
+use mailkit::html_text::{html_to_text, html_to_text_width};
+
+#[test]
+fn headings_and_paragraphs() {
+    let html = "<h1>Welcome</h1><p>First paragraph.</p><h2>Details</h2><p>Second</p><h3>Small</h3>";
+    assert_eq!(
+        html_to_text(html),
+        "Welcome\n=======\n\nFirst paragraph.\n\nDetails\n-------\n\nSecond\n\nSmall\n"
+    );
+}
+
+#[test]
+fn lists() {
+    let html = "<ul><li>Apples</li><li>Pears<ul><li>Conference</li></ul></li></ul>\
+                <ol start=\"3\"><li>Third</li><li>Fourth</li></ol>";
+    assert_eq!(
+        html_to_text(html),
+        "- Apples\n- Pears\n  - Conference\n\n3. Third\n4. Fourth\n"
+    );
+}
+
+#[test]
+fn links_and_images() {
+    let html = "<p><a href=\"https://example.com/a?x=1&amp;y=2\">Open</a>, \
+                <a href='https://example.com'>https://example.com</a>, \
+                <a href=\"mailto:help@example.com\">help@example.com</a>, \
+                <a href=\"#top\">top</a>, \
+                <a href=\"https://example.com/logo\"><img src=\"logo.png\" alt=\"Logo\"></a> \
+                <img src=\"spacer.gif\"></p>";
+    assert_eq!(
+        html_to_text_width(html, 0),
+        "Open (https://example.com/a?x=1&y=2), https://example.com, help@example.com, top, Logo (https://example.com/logo)\n"
+    );
+}
+
+#[test]
+fn tables_are_flattened() {
+    let html = "<p>Order</p><table><tr><th>Item</th><th>Qty</th></tr>\
+                <tr><td>Book</td><td>2</td></tr></table><p>Total</p>";
+    assert_eq!(html_to_text(html), "Order\n\nItem | Qty\nBook | 2\n\nTotal\n");
+}
+
+#[test]
+fn quotes_and_preformatted() {
+    let html = "<blockquote><p>Quoted text</p><p>More</p></blockquote><pre>  let x = 1;\n  let y = 2;</pre>";
+    assert_eq!(
+        html_to_text(html),
+        "> Quoted text\n>\n> More\n\n  let x = 1;\n  let y = 2;\n"
+    );
+}
+
+#[test]
+fn scripts_styles_and_comments_are_dropped() {
+    let html = "<!DOCTYPE html><html><head><title>T</title><style>p{}</style></head>\
+                <body><script>if (a < b) {}</script><!-- <p>hidden</p> --><p>Shown</p></body></html>";
+    assert_eq!(html_to_text(html), "Shown\n");
+}
+
+#[test]
+fn wraps_long_lines() {
+    let html = "<p>The quick brown fox jumps over the lazy dog</p>\
+                <ul><li>a list item that wraps</li></ul>\
+                <p>see https://example.com/a/very/long/url</p>";
+    assert_eq!(
+        html_to_text_width(html, 16),
+        "The quick brown\nfox jumps over\nthe lazy dog\n\n- a list item\n  that wraps\n\nsee\nhttps://example.com/a/very/long/url\n"
+    );
+    let long = "word ".repeat(40);
+    assert_eq!(html_to_text_width(&long, 0).lines().count(), 1);
+}
+
+#[test]
+fn breaks_and_entities() {
+    let html = "Fish &amp; chips<br>for&nbsp;two<br><br>Caf&#233; &#x263A; &unknown; AT&T";
+    assert_eq!(html_to_text(html), "Fish & chips\nfor two\n\nCafé ☺ &unknown; AT&T\n");
+}
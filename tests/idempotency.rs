@@ -0,0 +1,208 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{StubTransport, builder, sender};
+use mailkit::idempotency::{FileStore, IdempotencyStore, MemoryStore};
+use mailkit::outbox::Outbox;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{BulkMode, Email, ErrorKind, MailkitError};
+
+fn receipt(key: &str) -> Email {
+    Email::builder()
+        .to("rcpt@example.com")
+        .subject("Receipt")
+        .text("Thanks for your order")
+        .idempotency_key(key)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn duplicate_key_is_skipped() {
+    let transport = InMemoryTransport::new();
+    let sender = sender(transport.clone());
+
+    sender.deliver(&receipt("order-1")).unwrap();
+    let err = sender.deliver(&receipt("order-1")).unwrap_err();
+    assert!(matches!(&err, MailkitError::Duplicate(key) if key == "order-1"));
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+    assert!(err.is_permanent());
+
+    sender.deliver(&receipt("order-2")).unwrap();
+    let unkeyed = Email::builder().to("rcpt@example.com").text("hi").build().unwrap();
+    sender.deliver(&unkeyed).unwrap();
+    sender.deliver(&unkeyed).unwrap();
+    assert_eq!(transport.len(), 4);
+}
+
+#[tokio::test]
+async fn failed_send_releases_key() {
+    let transport = Arc::new(StubTransport::new().fail_first(1));
+    let sender = sender(transport.clone());
+
+    assert!(sender.deliver_async(&receipt("order-1")).await.is_err());
+    sender.deliver_async(&receipt("order-1")).await.unwrap();
+    assert_eq!(transport.mailer().len(), 1);
+}
+
+#[test]
+fn key_expires_after_window() {
+    let transport = InMemoryTransport::new();
+    let sender = builder(transport.clone())
+        .idempotency_window(Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+    sender.deliver(&receipt("order-1")).unwrap();
+    std::thread::sleep(Duration::from_millis(80));
+    sender.deliver(&receipt("order-1")).unwrap();
+    assert_eq!(transport.len(), 2);
+}
+
+#[tokio::test]
+async fn bulk_keys_are_per_recipient() {
+    let transport = InMemoryTransport::new();
+    let sender = sender(transport.clone());
+    let recipients = vec!["a@example.com".to_string(), "b@example.com".to_string()];
+    let email = receipt("newsletter-42");
+
+    let first = sender.deliver_bulk(&email, &recipients, BulkMode::ContinueOnError);
+    assert_eq!(first.sent(), 2);
+    let again = sender.deliver_bulk(&email, &recipients, BulkMode::ContinueOnError);
+    assert_eq!((again.sent(), again.failed(), again.skipped()), (0, 0, 2));
+    assert!(again.is_success());
+
+    // a duplicate is not an error, so it does not stop the rest
+    let mut more = recipients.clone();
+    more.insert(1, "c@example.com".to_string());
+    let stopping = sender
+        .deliver_bulk_async(&email, &more, BulkMode::StopOnFirstError)
+        .await;
+    assert_eq!((stopping.sent(), stopping.skipped()), (1, 2));
+    assert_eq!(transport.len(), 3);
+}
+
+#[test]
+fn file_store_is_shared_and_durable() {
+    let dir = std::env::temp_dir().join(format!("mailkit-idempotency-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let transport = InMemoryTransport::new();
+    let build = || {
+        builder(transport.clone())
+            .idempotency_store(FileStore::new(&dir))
+            .build()
+            .unwrap()
+    };
+
+    build().deliver(&receipt("order-1")).unwrap();
+    let restarted = build();
+    assert!(matches!(restarted.deliver(&receipt("order-1")), Err(MailkitError::Duplicate(_))));
+    assert_eq!(transport.len(), 1);
+
+    let store = FileStore::new(&dir);
+    assert!(store.insert("short-lived", Duration::ZERO).unwrap());
+    assert_eq!(store.prune().unwrap(), 1);
+    store.remove("order-1").unwrap();
+    assert!(store.insert("order-1", Duration::from_secs(60)).unwrap());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// The record files in `dir`.
+fn records(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn file_store_replaces_unreadable_records() {
+    let dir = std::env::temp_dir().join(format!("mailkit-idempotency-corrupt-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = FileStore::new(&dir);
+
+    assert!(store.insert("order-1", Duration::from_secs(60)).unwrap());
+    let [record] = &records(&dir)[..] else { panic!("expected one record") };
+    std::fs::write(record, "garbage").unwrap();
+    assert!(store.insert("order-1", Duration::from_secs(60)).unwrap());
+    assert!(!store.insert("order-1", Duration::from_secs(60)).unwrap());
+    // no temporary files are left behind
+    assert_eq!(records(&dir).len(), 1);
+
+    std::fs::write(record, "").unwrap();
+    assert_eq!(store.prune().unwrap(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn file_store_lets_one_racer_replace_an_expired_record() {
+    let dir = std::env::temp_dir().join(format!("mailkit-idempotency-race-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    for round in 0..20 {
+        let key = format!("order-{}", round);
+        assert!(FileStore::new(&dir).insert(&key, Duration::ZERO).unwrap());
+        let start = Arc::new(std::sync::Barrier::new(8));
+        let racers: Vec<_> = (0..8)
+            .map(|_| {
+                let (dir, key, start) = (dir.clone(), key.clone(), start.clone());
+                std::thread::spawn(move || {
+                    start.wait();
+                    FileStore::new(dir).insert(&key, Duration::from_secs(60)).unwrap()
+                })
+            })
+            .collect();
+        let winners = racers.into_iter().map(|r| r.join().unwrap()).filter(|won| *won).count();
+        assert_eq!(winners, 1, "round {}", round);
+    }
+    assert_eq!(records(&dir).len(), 20);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn file_store_keeps_records_of_other_keys() {
+    let dir = std::env::temp_dir().join(format!("mailkit-idempotency-other-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = FileStore::new(&dir);
+
+    assert!(store.insert("order-1", Duration::from_secs(60)).unwrap());
+    assert!(store.insert("order-2", Duration::from_secs(60)).unwrap());
+    assert_eq!(records(&dir).len(), 2);
+
+    // an expired record found under another key's name is left alone
+    let [first, second] = &records(&dir)[..] else { panic!("expected two records") };
+    let stored = std::fs::read_to_string(first).unwrap();
+    let key = stored.split_once('\n').unwrap().1;
+    std::fs::write(second, format!("0\n{}", key)).unwrap();
+    let other = if key == "order-1" { "order-2" } else { "order-1" };
+    assert!(store.insert(other, Duration::from_secs(60)).is_err());
+    store.remove(other).unwrap();
+    assert_eq!(std::fs::read_to_string(second).unwrap(), format!("0\n{}", key));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn memory_store_expires_keys() {
+    let store = MemoryStore::new();
+    assert!(store.insert("k", Duration::from_secs(60)).unwrap());
+    assert!(!store.insert("k", Duration::from_secs(60)).unwrap());
+    store.remove("k").unwrap();
+    assert!(store.insert("k", Duration::ZERO).unwrap());
+    assert!(store.insert("k", Duration::from_secs(60)).unwrap());
+}
+
+#[test]
+fn enqueue_checks_key() {
+    let dir = std::env::temp_dir().join(format!("mailkit-idempotency-outbox-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sender = builder(InMemoryTransport::new())
+        .outbox(Outbox::new(&dir))
+        .build()
+        .unwrap();
+
+    sender.enqueue(&receipt("order-1")).unwrap();
+    assert!(matches!(sender.enqueue(&receipt("order-1")), Err(MailkitError::Duplicate(_))));
+    assert_eq!(sender.outbox().unwrap().pending().unwrap().len(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{builder, raw, template_sender};
+use mailkit::transport::InMemoryTransport;
+use mailkit::{BulkMode, Email, MailkitError, json};
+
+const PNG: &[u8] = include_bytes!("files/logo.png");
+
+#[test]
+fn inline_images_are_related_to_the_html() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .subject("Logo")
+        .html(r#"<img src="cid:logo"><img src="cid:badge">"#)
+        .inline_image("logo", "tests/files/logo.png")
+        .inline_image_bytes("badge", PNG.to_vec(), "image/png")
+        .attachment("tests/files/sample.txt")
+        .build()
+        .unwrap();
+    template_sender(transport.clone()).deliver(&email).unwrap();
+
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.inline_content_ids(), ["logo", "badge"]);
+    assert_eq!(sent.attachment_names(), ["sample.txt"]);
+    assert!(sent.text().is_some());
+    let raw = raw(&transport.last().unwrap());
+    let mixed = raw.find("multipart/mixed").unwrap();
+    let alternative = raw.find("multipart/alternative").unwrap();
+    let related = raw.find("multipart/related").unwrap();
+    assert!(mixed < alternative && alternative < related);
+    assert!(raw.contains("Content-Disposition: inline"));
+}
+
+#[tokio::test]
+async fn async_build_reads_inline_images() {
+    let transport = InMemoryTransport::new();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .html(r#"<img src="cid:logo">"#)
+        .inline_image("logo", "tests/files/logo.png")
+        .build()
+        .unwrap();
+    template_sender(transport.clone()).deliver_async(&email).await.unwrap();
+
+    assert_eq!(transport.last().unwrap().inline_content_ids(), ["logo"]);
+    assert!(raw(&transport.last().unwrap()).contains("Content-Type: image/png"));
+}
+
+#[test]
+fn template_function_registers_images_once() {
+    let transport = InMemoryTransport::new();
+    let sender = template_sender(transport.clone());
+    let email = sender
+        .render_template("rcpt@example.com", "Hi", "logo.html", &json!({"name": "Ada"}))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(email.inline_images().len(), 1);
+    let image = &email.inline_images()[0];
+    assert_eq!(image.path(), Some("tests/files/logo.png"));
+    let cid = format!("cid:{}", image.content_id());
+    assert_eq!(email.html().unwrap().matches(&cid).count(), 2);
+
+    sender.deliver(&email).unwrap();
+    assert_eq!(transport.last().unwrap().inline_content_ids(), [image.content_id()]);
+}
+
+#[test]
+fn bulk_template_embeds_images() {
+    let transport = InMemoryTransport::new();
+    let recipients = vec![
+        ("ada@example.com".to_string(), json!({"name": "Ada"})),
+        ("alan@example.com".to_string(), json!({"name": "Alan"})),
+    ];
+    let report = template_sender(transport.clone()).send_bulk_template(recipients, "Hi", "logo.html", &json!({}), BulkMode::ContinueOnError);
+
+    assert_eq!(report.sent(), 2);
+    for message in transport.messages() {
+        assert_eq!(message.inline_content_ids().len(), 1);
+    }
+}
+
+#[test]
+fn missing_template_image_fails_to_render() {
+    let mut tera = tera::Tera::default();
+    tera.add_raw_template("missing.html", r#"<img src="{{ inline_image(path="tests/files/nope.png") }}">"#)
+        .unwrap();
+    let sender = builder(InMemoryTransport::new())
+        .tera(tera)
+        .build()
+        .unwrap();
+
+    let err = sender
+        .render_template("rcpt@example.com", "Hi", "missing.html", &json!({}))
+        .unwrap_err();
+    assert!(matches!(err, MailkitError::Tera(_)));
+}
+
+#[test]
+fn inline_images_are_validated() {
+    let no_html = Email::builder()
+        .to("rcpt@example.com")
+        .text("plain")
+        .inline_image("logo", "tests/files/logo.png")
+        .build();
+    assert!(matches!(no_html, Err(MailkitError::Validation(_))));
+
+    let bad_id = Email::builder()
+        .to("rcpt@example.com")
+        .html("<p>hi</p>")
+        .inline_image("<logo>", "tests/files/logo.png")
+        .build();
+    assert!(matches!(bad_id, Err(MailkitError::Validation(_))));
+
+    let bad_type = Email::builder()
+        .to("rcpt@example.com")
+        .html("<p>hi</p>")
+        .inline_image_bytes("logo", PNG.to_vec(), "not a type")
+        .build();
+    assert!(matches!(bad_type, Err(MailkitError::Validation(_))));
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::{StubTransport, builder, email};
+use mailkit::RateLimit;
+use mailkit::transport::InMemoryTransport;
+
+#[tokio::test]
+async fn bulk_respects_max_in_flight() {
+    let transport = Arc::new(StubTransport::new().delay(Duration::from_millis(10)));
+    let sender = builder(transport.clone())
+        .max_in_flight(3)
+        .build()
+        .unwrap();
+
+    let recipients: Vec<String> = (0..20).map(|i| format!("rcpt{}@example.com", i)).collect();
+    let report = sender
+        .send_bulk_async(recipients, "test", "body", None, None, None, false, false)
+        .await;
+
+    assert!(report.is_success());
+    assert_eq!(transport.mailer().len(), 20);
+    assert_eq!(transport.peak(), 3);
+}
+
+#[tokio::test]
+async fn single_sends_share_the_limit() {
+    let transport = Arc::new(StubTransport::new().delay(Duration::from_millis(10)));
+    let sender = builder(transport.clone())
+        .max_in_flight(2)
+        .build()
+        .unwrap();
+
+    let email = email("limits");
+    let sends: Vec<_> = (0..6).map(|_| sender.deliver_async(&email)).collect();
+    for res in futures::future::join_all(sends).await {
+        res.unwrap();
+    }
+    assert_eq!(transport.peak(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sync_and_async_sends_share_the_limit() {
+    let transport = Arc::new(StubTransport::new().delay(Duration::from_millis(20)));
+    let sender = Arc::new(builder(transport.clone()).max_in_flight(1).build().unwrap());
+
+    let blocking = {
+        let sender = sender.clone();
+        tokio::task::spawn_blocking(move || {
+            for _ in 0..3 {
+                sender.deliver(&email("limits")).unwrap();
+            }
+        })
+    };
+    let email = email("limits");
+    let sends: Vec<_> = (0..3).map(|_| sender.deliver_async(&email)).collect();
+    for res in futures::future::join_all(sends).await {
+        res.unwrap();
+    }
+    blocking.await.unwrap();
+
+    assert_eq!(transport.mailer().len(), 6);
+    assert_eq!(transport.peak(), 1);
+}
+
+#[tokio::test]
+async fn bulk_starts_a_bounded_number_of_sends_by_default() {
+    let transport = Arc::new(StubTransport::new().delay(Duration::from_millis(10)));
+    let sender = builder(transport.clone()).build().unwrap();
+
+    let recipients: Vec<String> = (0..40).map(|i| format!("rcpt{}@example.com", i)).collect();
+    let report = sender
+        .send_bulk_async(recipients, "test", "body", None, None, None, false, false)
+        .await;
+
+    assert_eq!(report.sent(), 40);
+    assert_eq!(transport.peak(), 16);
+}
+
+#[test]
+fn rate_limit_spaces_out_sends() {
+    let mailer = InMemoryTransport::new();
+    let sender = builder(mailer.clone())
+        .rate_limit(RateLimit::new(2, Duration::from_millis(200)))
+        .rate_limit(RateLimit::per_day(500))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    for _ in 0..2 {
+        sender.deliver(&email("limits")).unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    // one token every 100ms after the initial burst
+    for _ in 0..2 {
+        sender.deliver(&email("limits")).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(190));
+    assert_eq!(mailer.len(), 4);
+}
+
+#[tokio::test]
+async fn rate_limit_applies_to_async_bulk() {
+    let mailer = InMemoryTransport::new();
+    let sender = builder(mailer.clone())
+        .rate_limit(RateLimit::new(1, Duration::from_millis(50)))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    let recipients: Vec<String> = (0..4).map(|i| format!("rcpt{}@example.com", i)).collect();
+    let report = sender
+        .send_bulk_async(recipients, "test", "body", None, None, None, false, false)
+        .await;
+    assert_eq!(report.sent(), 4);
+    assert!(start.elapsed() >= Duration::from_millis(140));
+}
+
+#[tokio::test]
+async fn cancelled_waits_use_no_tokens() {
+    let mailer = InMemoryTransport::new();
+    let sender = builder(mailer.clone())
+        .rate_limit(RateLimit::new(1, Duration::from_millis(200)))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    let email = email("limits");
+    sender.deliver_async(&email).await.unwrap();
+    for _ in 0..5 {
+        let res = tokio::time::timeout(Duration::from_millis(10), sender.deliver_async(&email)).await;
+        assert!(res.is_err());
+    }
+
+    // the abandoned sends took nothing, so the next one waits for one token
+    sender.deliver_async(&email).await.unwrap();
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert_eq!(mailer.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn sync_waits_do_not_stall_other_tasks() {
+    let mailer = InMemoryTransport::new();
+    let sender = Arc::new(
+        builder(mailer.clone())
+            .rate_limit(RateLimit::new(1, Duration::from_millis(300)))
+            .build()
+            .unwrap(),
+    );
+    sender.deliver(&email("limits")).unwrap();
+
+    let waiting = {
+        let sender = sender.clone();
+        tokio::spawn(async move { sender.deliver(&email("limits")).unwrap() })
+    };
+    // let the only worker pick up the send and start waiting for a token
+    std::thread::sleep(Duration::from_millis(20));
+
+    let start = Instant::now();
+    tokio::spawn(async {}).await.unwrap();
+    assert!(start.elapsed() < Duration::from_millis(150));
+
+    waiting.await.unwrap();
+    assert_eq!(mailer.len(), 2);
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::template_sender;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{Email, json};
+
+#[test]
+fn captures_send_template_output() {
+    let transport = InMemoryTransport::new();
+    let sender = template_sender(transport.clone());
+
+    sender
+        .send_template(
+            "alice@example.com",
+            "Welcome ✉",
+            "test.txt",
+            &json!({"name": "Alice"}),
+            None::<Vec<String>>,
+            Some(vec!["audit@example.com"]),
+            Some(&["tests/files/sample.txt".to_string()]),
+            false,
+        )
+        .unwrap();
+
+    assert_eq!(transport.len(), 1);
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.from().as_deref(), Some("sender@example.com"));
+    assert_eq!(sent.subject().as_deref(), Some("Welcome ✉"));
+    assert_eq!(sent.html().unwrap().trim(), "Hello Alice");
+    assert_eq!(sent.text().as_deref(), Some("Hello Alice\n"));
+    assert_eq!(sent.attachment_names(), ["sample.txt"]);
+    assert_eq!(sent.header("to").as_deref(), Some("alice@example.com"));
+    assert!(sent.header("Bcc").is_none());
+    assert_eq!(transport.messages_to("AUDIT@example.com").len(), 1);
+}
+
+#[tokio::test]
+async fn queries_and_clear() {
+    let transport = InMemoryTransport::new();
+    let sender = template_sender(transport.clone());
+
+    for to in ["a@example.com", "b@example.com", "a@example.com"] {
+        let email = Email::builder()
+            .to(to)
+            .subject(format!("for {}", to))
+            .text("line one\nline two ünïcode")
+            .header("X-Campaign", "spring")
+            .build()
+            .unwrap();
+        sender.deliver_async(&email).await.unwrap();
+    }
+
+    let to_a = transport.messages_to("a@example.com");
+    assert_eq!(to_a.len(), 2);
+    assert!(to_a.iter().all(|m| m.subject().as_deref() == Some("for a@example.com")));
+
+    let last = transport.last().unwrap();
+    assert_eq!(last.text().as_deref(), Some("line one\nline two ünïcode"));
+    assert!(last.headers().iter().any(|(k, v)| k == "X-Campaign" && v == "spring"));
+
+    transport.clear();
+    assert!(transport.is_empty());
+    assert!(transport.last().is_none());
+}
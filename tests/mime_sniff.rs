@@ -0,0 +1,143 @@
+mod common;
+
+use common::builder;
+use mailkit::attachment::MimePolicy;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{AttachmentSource, Email, MailkitError};
+
+const PDF: &[u8] = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n1 0 obj\n";
+const PNG: &[u8] = include_bytes!("files/logo.png");
+
+fn zip_with(name: &str) -> Vec<u8> {
+    let mut data = b"PK\x03\x04".to_vec();
+    data.extend_from_slice(&[0; 26]);
+    data.extend_from_slice(name.as_bytes());
+    data.extend_from_slice(&[0x78, 0x9c, 0x01, 0x02, 0x03]);
+    data
+}
+
+/// Sends `source` and returns the content type its part went out with.
+fn sent_type(policy: MimePolicy, source: AttachmentSource) -> Result<String, MailkitError> {
+    let transport = InMemoryTransport::new();
+    let sender = builder(transport.clone())
+        .mime_policy(policy)
+        .build()
+        .unwrap();
+    let name = source.name().to_string();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .text("attached")
+        .attachment(source)
+        .build()?;
+    sender.deliver(&email)?;
+
+    let raw = String::from_utf8_lossy(transport.last().unwrap().raw()).into_owned();
+    let part = &raw[raw.find(&format!("filename=\"{}\"", name)).unwrap()..];
+    let line = part.lines().find(|l| l.starts_with("Content-Type: ")).unwrap();
+    Ok(line["Content-Type: ".len()..].to_string())
+}
+
+fn detected(data: &[u8]) -> String {
+    sent_type(MimePolicy::TrustContent, AttachmentSource::bytes(data, "upload")).unwrap()
+}
+
+#[test]
+fn detects_files_without_extension() {
+    assert_eq!(detected(PDF), "application/pdf");
+    assert_eq!(detected(PNG), "image/png");
+    assert_eq!(detected(&[0xff, 0xd8, 0xff, 0xe0, 0, 0x10]), "image/jpeg");
+    assert_eq!(detected(b"GIF89a\x01\x00\x01\x00"), "image/gif");
+    assert_eq!(detected(&zip_with("notes.txt")), "application/zip");
+    assert_eq!(
+        detected(&zip_with("word/document.xml")),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    );
+    assert_eq!(
+        detected(&zip_with("xl/workbook.xml")),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    );
+    assert_eq!(
+        detected(&zip_with("mimetypeapplication/vnd.oasis.opendocument.text")),
+        "application/vnd.oasis.opendocument.text"
+    );
+    assert_eq!(
+        detected(&[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1, 0]),
+        "application/x-ole-storage"
+    );
+    assert_eq!(detected(b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n"), "text/calendar");
+    assert_eq!(detected(b"id,name,total\n1,Ada,9.99\n2,Alan,5.00\n"), "text/csv");
+    assert_eq!(detected("Grüße aus Köln\n".as_bytes()), "text/plain");
+    assert_eq!(detected(&[0x00, 0x01, 0x02, 0xfe]), "application/octet-stream");
+}
+
+#[test]
+fn mismatches_follow_the_policy() {
+    let misnamed = || AttachmentSource::bytes(PDF, "photo.png");
+    assert_eq!(sent_type(MimePolicy::TrustContent, misnamed()).unwrap(), "application/pdf");
+    assert_eq!(sent_type(MimePolicy::TrustExtension, misnamed()).unwrap(), "image/png");
+    assert!(matches!(
+        sent_type(MimePolicy::RejectMismatch, misnamed()),
+        Err(MailkitError::Validation(_))
+    ));
+}
+
+#[test]
+fn compatible_content_keeps_the_extension_type() {
+    let policy = MimePolicy::RejectMismatch;
+    assert_eq!(
+        sent_type(policy, AttachmentSource::path("tests/files/sample.html")).unwrap(),
+        "text/html"
+    );
+    assert_eq!(
+        sent_type(policy, AttachmentSource::bytes(zip_with("content.xml"), "report.docx")).unwrap(),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    );
+    assert_eq!(
+        sent_type(policy, AttachmentSource::bytes(&[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1][..], "old.xls"))
+            .unwrap(),
+        "application/vnd.ms-excel"
+    );
+    assert_eq!(
+        sent_type(policy, AttachmentSource::bytes(&b"a,b\n1,2\n"[..], "data.txt")).unwrap(),
+        "text/plain"
+    );
+}
+
+#[test]
+fn text_content_keeps_textual_extension_types() {
+    const EML: &[u8] = b"From: ada@example.com\r\nTo: alan@example.com\r\nSubject: Hi\r\n\r\nHello\r\n";
+    const RTF: &[u8] = b"{\\rtf1\\ansi{\\fonttbl\\f0\\fswiss Helvetica;}\\f0 Hello}";
+    for policy in [MimePolicy::TrustContent, MimePolicy::RejectMismatch] {
+        assert_eq!(
+            sent_type(policy, AttachmentSource::bytes(EML, "forwarded.eml")).unwrap(),
+            "message/rfc822"
+        );
+        assert_eq!(
+            sent_type(policy, AttachmentSource::bytes(RTF, "letter.rtf")).unwrap(),
+            "application/rtf"
+        );
+        assert_eq!(
+            sent_type(policy, AttachmentSource::bytes(&b"#!/bin/sh\necho hi\n"[..], "run.sh")).unwrap(),
+            "application/x-sh"
+        );
+    }
+    // a binary signature still contradicts a textual extension
+    assert_eq!(
+        sent_type(MimePolicy::TrustContent, AttachmentSource::bytes(PDF, "forwarded.eml")).unwrap(),
+        "application/pdf"
+    );
+}
+
+#[test]
+fn explicit_content_type_is_not_checked() {
+    let source = AttachmentSource::bytes(PDF, "photo.png").content_type("image/png");
+    assert_eq!(sent_type(MimePolicy::RejectMismatch, source).unwrap(), "image/png");
+}
+
+#[test]
+fn parses_policy_names() {
+    assert_eq!("extension".parse::<MimePolicy>().unwrap(), MimePolicy::TrustExtension);
+    assert_eq!(" Content ".parse::<MimePolicy>().unwrap(), MimePolicy::TrustContent);
+    assert_eq!("reject-mismatch".parse::<MimePolicy>().unwrap(), MimePolicy::RejectMismatch);
+    assert!(matches!("sometimes".parse::<MimePolicy>(), Err(MailkitError::Validation(_))));
+}
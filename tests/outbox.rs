@@ -0,0 +1,197 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use common::{StubTransport, builder, email};
+use mailkit::outbox::Outbox;
+use mailkit::retry::RetryPolicy;
+use mailkit::transport::{AsyncTransport, InMemoryTransport, Transport};
+use mailkit::{EmailSender, MailkitError};
+
+fn spool(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mailkit-outbox-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn outbox(dir: &PathBuf, attempts: u32) -> Outbox {
+    Outbox::new(dir).retry(
+        RetryPolicy::new()
+            .max_attempts(attempts)
+            .base_delay(Duration::ZERO)
+            .jitter(false),
+    )
+}
+
+fn sender<T: Transport + AsyncTransport + 'static>(transport: Arc<T>, outbox: Outbox) -> EmailSender {
+    builder(transport)
+        .outbox(outbox)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn enqueued_message_is_delivered() {
+    let dir = spool("deliver");
+    let transport = Arc::new(InMemoryTransport::new());
+    let sender = sender(transport.clone(), outbox(&dir, 3));
+
+    let id = sender.enqueue(&email("Queued")).unwrap();
+    let pending = sender.outbox().unwrap().pending().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id(), id);
+    assert_eq!(pending[0].to(), ["rcpt@example.com"]);
+    assert!(transport.is_empty());
+
+    assert_eq!(sender.process_outbox().await.unwrap(), 1);
+    assert_eq!(transport.last().unwrap().subject().as_deref(), Some("Queued"));
+    assert_eq!(transport.last().unwrap().header("Message-ID").as_deref(), Some(pending[0].message_id()));
+    assert!(sender.outbox().unwrap().pending().unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn failures_are_retried_then_dead_lettered() {
+    let dir = spool("dead");
+    let transport = Arc::new(StubTransport::new().fail_first(2));
+    let sender = sender(transport.clone(), outbox(&dir, 2));
+    sender.enqueue(&email("Queued")).unwrap();
+
+    sender.process_outbox().await.unwrap();
+    let pending = sender.outbox().unwrap().pending().unwrap();
+    assert_eq!(pending[0].attempts(), 1);
+    assert!(pending[0].last_error().is_some());
+
+    sender.process_outbox().await.unwrap();
+    assert!(sender.outbox().unwrap().pending().unwrap().is_empty());
+    let dead = sender.outbox().unwrap().dead().unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].attempts(), 2);
+
+    sender.outbox().unwrap().requeue(dead[0].id()).unwrap();
+    assert_eq!(sender.process_outbox().await.unwrap(), 1);
+    assert_eq!(transport.mailer().len(), 1);
+    assert!(sender.outbox().unwrap().dead().unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn permanent_failure_is_dead_lettered_at_once() {
+    let dir = spool("permanent");
+    let transport = Arc::new(InMemoryTransport::new());
+    let sender = sender(transport, outbox(&dir, 5));
+    sender.enqueue(&email("Queued")).unwrap();
+    // corrupt the envelope so the entry cannot be sent
+    let entry = std::fs::read_dir(dir.join("queue")).unwrap().next().unwrap().unwrap().path();
+    let data = std::fs::read_to_string(&entry).unwrap();
+    std::fs::write(&entry, data.replacen("rcpt@example.com", "not an address", 1)).unwrap();
+
+    assert_eq!(sender.process_outbox().await.unwrap(), 0);
+    let dead = sender.outbox().unwrap().dead().unwrap();
+    assert_eq!(dead.len(), 1);
+    assert!(dead[0].last_error().is_some());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn interrupted_sends_are_recovered_on_restart() {
+    let dir = spool("recover");
+    let first = sender(Arc::new(InMemoryTransport::new()), outbox(&dir, 3));
+    let id = first.enqueue(&email("Queued")).unwrap();
+    drop(first);
+
+    // simulate a crash while the message was being sent
+    let name = format!("{}.msg", id);
+    std::fs::rename(dir.join("queue").join(&name), dir.join("sending").join(&name)).unwrap();
+    std::fs::write(dir.join("tmp").join("partial.msg"), "{").unwrap();
+
+    let transport = Arc::new(InMemoryTransport::new());
+    let restarted = sender(transport.clone(), outbox(&dir, 3));
+    assert_eq!(restarted.outbox().unwrap().pending().unwrap().len(), 1);
+    assert_eq!(std::fs::read_dir(dir.join("tmp")).unwrap().count(), 0);
+
+    restarted.process_outbox().await.unwrap();
+    assert_eq!(transport.len(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn worker_drains_queue_in_background() {
+    let dir = spool("worker");
+    let transport = Arc::new(StubTransport::new().fail_first(1));
+    let sender = Arc::new(sender(
+        transport.clone(),
+        outbox(&dir, 3).poll_interval(Duration::from_secs(60)),
+    ));
+    let worker = sender.spawn_outbox_worker().unwrap();
+
+    sender.enqueue(&email("Queued")).unwrap();
+    sender.enqueue(&email("Queued")).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while transport.mailer().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("worker did not deliver queued messages");
+
+    worker.shutdown().await;
+    assert!(sender.outbox().unwrap().pending().unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn enqueue_requires_outbox() {
+    let sender = common::sender(InMemoryTransport::new());
+    assert!(matches!(sender.enqueue(&email("Queued")), Err(MailkitError::Validation(_))));
+}
+
+#[tokio::test]
+async fn scheduled_message_waits_until_due() {
+    let dir = spool("schedule");
+    let transport = Arc::new(InMemoryTransport::new());
+    let sender = sender(transport.clone(), outbox(&dir, 3));
+    let outbox = sender.outbox().unwrap();
+
+    let at = SystemTime::now() + Duration::from_secs(24 * 60 * 60);
+    let later = sender.enqueue_at(&email("Queued"), at).unwrap();
+    let cancelled = sender.enqueue_after(&email("Queued"), Duration::from_secs(60)).unwrap();
+    assert_eq!(sender.process_outbox().await.unwrap(), 0);
+    let entry = outbox.pending().unwrap().into_iter().find(|e| e.id() == later).unwrap();
+    assert!(entry.next_attempt() > SystemTime::now() + Duration::from_secs(23 * 60 * 60));
+
+    assert!(outbox.cancel(&cancelled).unwrap());
+    assert!(!outbox.cancel(&cancelled).unwrap());
+    assert!(outbox.reschedule(&later, SystemTime::now()).unwrap());
+    assert_eq!(sender.process_outbox().await.unwrap(), 1);
+    assert_eq!(transport.len(), 1);
+    assert!(!outbox.reschedule(&later, SystemTime::now()).unwrap());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn worker_sends_when_due() {
+    let dir = spool("due");
+    let transport = Arc::new(InMemoryTransport::new());
+    let sender = Arc::new(sender(
+        transport.clone(),
+        outbox(&dir, 3).poll_interval(Duration::from_secs(60)),
+    ));
+    let worker = sender.spawn_outbox_worker().unwrap();
+
+    sender.enqueue_after(&email("Queued"), Duration::from_millis(200)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(transport.is_empty());
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while transport.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("scheduled message was not sent");
+
+    worker.shutdown().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{MockSmtp, smtp_builder};
+use mailkit::{Email, EmailSender, PoolConfig};
+
+fn sender(port: u16, pool: PoolConfig) -> EmailSender {
+    smtp_builder(port)
+        .pool(pool)
+        .build()
+        .unwrap()
+}
+
+fn email(i: usize) -> Email {
+    Email::builder()
+        .to(format!("rcpt{}@example.com", i))
+        .subject("pooled")
+        .text("body")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn reuses_connection() {
+    let server = MockSmtp::start();
+    let sender = sender(server.port, PoolConfig::new());
+    for i in 0..3 {
+        sender.deliver(&email(i)).unwrap();
+    }
+    assert_eq!(server.messages().len(), 3);
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn max_messages_per_connection() {
+    let server = MockSmtp::start();
+    let sender = sender(server.port, PoolConfig::new().max_messages_per_connection(2));
+    for i in 0..3 {
+        sender.deliver(&email(i)).unwrap();
+    }
+    assert_eq!(server.connections(), 2);
+}
+
+#[test]
+fn pooling_disabled() {
+    let server = MockSmtp::start();
+    let sender = sender(server.port, PoolConfig::new().max_idle(0));
+    for i in 0..3 {
+        sender.deliver(&email(i)).unwrap();
+    }
+    assert_eq!(server.connections(), 3);
+}
+
+#[tokio::test]
+async fn async_reuse_and_close() {
+    let server = MockSmtp::start();
+    let sender = sender(server.port, PoolConfig::new());
+    sender.deliver_async(&email(0)).await.unwrap();
+    sender.deliver_async(&email(1)).await.unwrap();
+    assert_eq!(server.connections(), 1);
+
+    sender.close_async().await;
+    sender.deliver_async(&email(2)).await.unwrap();
+    assert_eq!(server.connections(), 2);
+    assert_eq!(server.messages().len(), 3);
+}
+
+#[tokio::test]
+async fn close_async_drains_sync_connections() {
+    let server = MockSmtp::start();
+    let sender = std::sync::Arc::new(sender(server.port, PoolConfig::new()));
+    let blocking = sender.clone();
+    tokio::task::spawn_blocking(move || blocking.deliver(&email(0)).unwrap())
+        .await
+        .unwrap();
+    sender.deliver_async(&email(1)).await.unwrap();
+    assert_eq!(server.connections(), 2);
+
+    sender.close_async().await;
+    let blocking = sender.clone();
+    tokio::task::spawn_blocking(move || blocking.deliver(&email(2)).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(server.connections(), 3);
+}
+
+#[test]
+fn rejected_message_keeps_the_connection() {
+    let server = MockSmtp::start();
+    server.reply_to_data("550 5.7.1 Message rejected");
+    let sender = sender(server.port, PoolConfig::new());
+
+    let err = sender.deliver(&email(0)).unwrap_err();
+    assert_eq!(err.smtp_code(), Some(550));
+    sender.deliver(&email(1)).unwrap();
+    assert_eq!(server.connections(), 1);
+    assert_eq!(server.messages().len(), 1);
+}
+
+#[tokio::test]
+async fn async_rejected_message_keeps_the_connection() {
+    let server = MockSmtp::start();
+    server.reply_to_data("451 4.7.1 Greylisted, try again");
+    let sender = sender(server.port, PoolConfig::new());
+
+    assert!(sender.deliver_async(&email(0)).await.unwrap_err().is_transient());
+    sender.deliver_async(&email(1)).await.unwrap();
+    assert_eq!(server.connections(), 1);
+    assert_eq!(server.messages().len(), 1);
+}
+
+#[test]
+fn closing_reply_drops_the_connection() {
+    let server = MockSmtp::start();
+    server.reply_to_data("421 4.3.0 Shutting down");
+    let sender = sender(server.port, PoolConfig::new());
+
+    sender.deliver(&email(0)).unwrap_err();
+    sender.deliver(&email(1)).unwrap();
+    assert_eq!(server.connections(), 2);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::template_sender;
+use mailkit::recipients::RecipientList;
+use mailkit::transport::InMemoryTransport;
+use mailkit::{BulkMode, JsonValue, json};
+
+fn field<'a>(ctx: &'a JsonValue, key: &str) -> &'a JsonValue {
+    &ctx.as_object().unwrap()[key]
+}
+
+#[test]
+fn loads_csv_with_row_errors() {
+    let list = RecipientList::from_csv_path("tests/files/recipients.csv").unwrap();
+
+    let addresses: Vec<&str> = list.recipients().iter().map(|(a, _)| a.as_str()).collect();
+    assert_eq!(addresses, ["ada@example.com", "alan@example.com", "grace@example.com"]);
+
+    let alan = &list.recipients()[1].1;
+    assert_eq!(field(alan, "first_name"), &json!("Alan \"AT\" Turing"));
+    assert_eq!(field(alan, "plan"), &json!("free"));
+    assert_eq!(field(&list.recipients()[2].1, "first_name"), &json!("Grace,\nHopper"));
+
+    let errors: Vec<(usize, &str)> = list.errors().iter().map(|e| (e.line(), e.message())).collect();
+    assert_eq!(
+        errors,
+        [
+            (3, "invalid email address: \"not-an-email\""),
+            (5, "expected 3 fields, found 2"),
+        ]
+    );
+}
+
+#[test]
+fn csv_requires_email_column() {
+    assert!(RecipientList::from_csv_str("name,plan\nAda,pro\n").is_err());
+    assert!(RecipientList::from_csv_str("").is_err());
+
+    let list = RecipientList::from_csv_str("email,name\n\"ada@example.com,Ada\n").unwrap();
+    assert!(list.is_empty());
+    assert_eq!(list.errors()[0].to_string(), "line 2: unterminated quoted field");
+}
+
+#[tokio::test]
+async fn loads_jsonl_with_row_errors() {
+    let list = RecipientList::from_jsonl_path_async("tests/files/recipients.jsonl")
+        .await
+        .unwrap();
+
+    assert_eq!(list.len(), 2);
+    assert_eq!(field(&list.recipients()[0].1, "seats"), &json!(3.0));
+    assert_eq!(field(&list.recipients()[1].1, "tags"), &json!(["beta"]));
+
+    let lines: Vec<usize> = list.errors().iter().map(|e| e.line()).collect();
+    assert_eq!(lines, [2, 4, 5]);
+    assert!(list.errors()[0].message().starts_with("invalid JSON"));
+}
+
+#[test]
+fn feeds_bulk_template() {
+    let mailer = InMemoryTransport::new();
+    let sender = template_sender(mailer.clone());
+
+    let list = RecipientList::from_csv_path("tests/files/recipients.csv").unwrap();
+    let report = sender.send_bulk_template(
+        list,
+        "Your {{ plan }} plan",
+        "test.txt",
+        &json!({"name": "there"}),
+        BulkMode::ContinueOnError,
+    );
+
+    assert_eq!(report.sent(), 3);
+    let ada = mailer.messages_to("ada@example.com").remove(0);
+    assert_eq!(ada.subject().as_deref(), Some("Your pro plan"));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{MockSmtp, email, smtp_builder};
+use mailkit::retry::{RetryClass, RetryPolicy};
+use mailkit::{EmailSender, MailkitError};
+
+fn sender(port: u16, retry: RetryPolicy) -> EmailSender {
+    smtp_builder(port)
+        .retry(retry)
+        .build()
+        .unwrap()
+}
+
+fn fast() -> RetryPolicy {
+    RetryPolicy::new().base_delay(Duration::from_millis(1)).jitter(false)
+}
+
+#[test]
+fn retries_temporary_rejection() {
+    let server = MockSmtp::start();
+    server.reply_to_data("451 4.7.1 Greylisted, try again");
+    server.reply_to_data("421 4.3.0 Busy");
+    let sender = sender(server.port, fast());
+
+    sender.deliver(&email("retry")).unwrap();
+    assert_eq!(server.data_attempts(), 3);
+    assert_eq!(server.messages().len(), 1);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let server = MockSmtp::start();
+    for _ in 0..3 {
+        server.reply_to_data("451 4.7.1 Greylisted, try again");
+    }
+    let sender = sender(server.port, fast().max_attempts(2));
+
+    let err = sender.deliver_async(&email("retry")).await.unwrap_err();
+    assert_eq!(RetryClass::of(&err), Some(RetryClass::TemporaryRejection));
+    assert_eq!(server.data_attempts(), 2);
+}
+
+#[test]
+fn permanent_failure_is_not_retried() {
+    let server = MockSmtp::start();
+    server.reply_to_data("550 5.1.1 No such user");
+    let sender = sender(server.port, fast());
+
+    let err = sender.deliver(&email("retry")).unwrap_err();
+    assert!(matches!(&err, MailkitError::Smtp(e) if e.is_permanent()));
+    assert_eq!(RetryClass::of(&err), None);
+    assert_eq!(server.data_attempts(), 1);
+}
+
+#[test]
+fn retry_classes_are_configurable() {
+    let server = MockSmtp::start();
+    server.reply_to_data("451 4.7.1 Greylisted, try again");
+    let sender = sender(server.port, fast().retry_on(&[RetryClass::Connection]));
+
+    assert!(sender.deliver(&email("retry")).is_err());
+    assert_eq!(server.data_attempts(), 1);
+}
+
+#[test]
+fn classifies_io_errors() {
+    let io = |kind| MailkitError::Io(std::io::Error::from(kind));
+    assert_eq!(RetryClass::of(&io(std::io::ErrorKind::TimedOut)), Some(RetryClass::Timeout));
+    assert_eq!(
+        RetryClass::of(&io(std::io::ErrorKind::ConnectionReset)),
+        Some(RetryClass::Connection)
+    );
+    assert_eq!(RetryClass::of(&io(std::io::ErrorKind::NotFound)), None);
+    assert_eq!(RetryClass::of(&MailkitError::Validation("bad".into())), None);
+}
+
+#[test]
+fn backoff_grows_and_is_capped() {
+    let policy = RetryPolicy::new()
+        .base_delay(Duration::from_millis(100))
+        .max_delay(Duration::from_millis(350))
+        .jitter(false);
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(2), Duration::from_millis(200));
+    assert_eq!(policy.delay(3), Duration::from_millis(350));
+
+    let jittered = policy.jitter(true);
+    for _ in 0..50 {
+        let d = jittered.delay(2);
+        assert!(d >= Duration::from_millis(100) && d <= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,72 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use mailkit::transport::SendmailTransport;
+use mailkit::{Email, EmailSender, MailkitError};
+
+/// Writes a stub sendmail script; it is run through `/bin/sh` so the test
+/// never executes a file it just wrote.
+fn stub(name: &str, body: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mailkit-sendmail-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("sendmail.sh"), body.replace("$DIR", &dir.display().to_string())).unwrap();
+    dir
+}
+
+fn sender(dir: &Path) -> EmailSender {
+    common::sender(SendmailTransport::with_command("/bin/sh").arg(dir.join("sendmail.sh").display().to_string()))
+}
+
+fn email() -> Email {
+    Email::builder()
+        .to("rcpt@example.com")
+        .bcc("hidden@example.com")
+        .subject("Sendmail")
+        .text("body")
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn pipes_message_with_envelope_args() {
+    let dir = stub("ok", "echo \"$@\" >> $DIR/args\ncat >> $DIR/message\n");
+    let sender = sender(&dir);
+
+    sender.deliver(&email()).unwrap();
+    sender.deliver_async(&email()).await.unwrap();
+
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    let expected = "-i -f sender@example.com -- rcpt@example.com hidden@example.com\n";
+    assert_eq!(args, expected.repeat(2));
+    let message = std::fs::read_to_string(dir.join("message")).unwrap();
+    assert_eq!(message.matches("Subject: Sendmail").count(), 2);
+    assert!(!message.contains("hidden@example.com"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn reports_exit_status_and_stderr() {
+    let dir = stub("fail", "echo 'unknown user' >&2\nexit 67\n");
+    let sender = sender(&dir);
+
+    for res in [sender.deliver(&email()), sender.deliver_async(&email()).await] {
+        match res {
+            Err(MailkitError::Sendmail { status, stderr }) => {
+                assert_eq!(status, Some(67));
+                assert_eq!(stderr, "unknown user");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_binary_is_io_error() {
+    let sender = common::sender(SendmailTransport::with_command("/nonexistent/sendmail"));
+    assert!(matches!(sender.deliver(&email()), Err(MailkitError::Io(_))));
+}
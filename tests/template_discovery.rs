@@ -0,0 +1,70 @@
+mod common;
+
+use common::{builder, template_sender};
+use mailkit::transport::InMemoryTransport;
+use mailkit::{MailkitError, json};
+
+#[test]
+fn send_template_uses_text_and_front_matter_subject() {
+    let transport = InMemoryTransport::new();
+    template_sender(transport.clone())
+        .send_template(
+            "ada@example.com",
+            "unused",
+            "welcome.html",
+            &json!({"name": "Ada & Co"}),
+            None::<Vec<String>>,
+            None::<Vec<String>>,
+            None,
+            false,
+        )
+        .unwrap();
+
+    let sent = transport.last().unwrap();
+    assert_eq!(sent.subject().as_deref(), Some("Welcome, Ada & Co!"));
+    assert_eq!(sent.text().as_deref(), Some("Hi Ada & Co, thanks for signing up.\n"));
+    let html = sent.html().unwrap();
+    assert!(html.starts_with("<h1>Hi Ada &amp; Co</h1>"));
+    assert!(!html.contains("subject:"));
+}
+
+#[test]
+fn subject_file_is_rendered() {
+    let transport = InMemoryTransport::new();
+    let email = template_sender(transport.clone())
+        .render_template("ada@example.com", "unused", "receipt.html", &json!({"order": "42"}))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(email.subject(), "Your receipt for order 42");
+    assert!(email.html().unwrap().contains("order 42"));
+}
+
+#[test]
+fn subject_argument_is_the_fallback() {
+    let transport = InMemoryTransport::new();
+    let email = template_sender(transport.clone())
+        .render_template("ada@example.com", "Hi", "merge.html", &json!({"name": "Ada", "product": "MailKit"}))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(email.subject(), "Hi");
+    assert_eq!(email.text(), None);
+}
+
+#[test]
+fn unclosed_front_matter_is_rejected() {
+    let mut tera = tera::Tera::default();
+    tera.add_raw_template("broken.html", "---\nsubject: Hi\n<p>body</p>").unwrap();
+    let sender = builder(InMemoryTransport::new())
+        .tera(tera)
+        .build()
+        .unwrap();
+
+    let err = sender
+        .render_template("ada@example.com", "Hi", "broken.html", &json!({}))
+        .unwrap_err();
+    assert!(matches!(err, MailkitError::Validation(_)));
+}
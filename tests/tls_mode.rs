@@ -0,0 +1,79 @@
+mod common;
+
+use common::{MockSmtp, smtp_builder};
+use mailkit::{Email, EmailSender, TlsMode};
+
+#[test]
+fn parse_tls_mode() {
+    assert_eq!("smtps".parse::<TlsMode>().unwrap(), TlsMode::Wrapper);
+    assert_eq!("STARTTLS".parse::<TlsMode>().unwrap(), TlsMode::StartTlsRequired);
+    assert_eq!("opportunistic".parse::<TlsMode>().unwrap(), TlsMode::StartTlsOpportunistic);
+    assert_eq!("none".parse::<TlsMode>().unwrap(), TlsMode::None);
+    assert!("sometimes".parse::<TlsMode>().is_err());
+}
+
+#[test]
+fn plaintext_delivery() {
+    let server = MockSmtp::start();
+    let sender = smtp_builder(server.port).build().unwrap();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .subject("Plain")
+        .text("over plaintext")
+        .build()
+        .unwrap();
+
+    sender.deliver(&email).unwrap();
+
+    let messages = server.messages();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].rcpt, ["rcpt@example.com"]);
+    assert!(messages[0].data.contains("Subject: Plain"));
+}
+
+#[tokio::test]
+async fn opportunistic_falls_back_to_plaintext() {
+    let server = MockSmtp::start();
+    let sender = EmailSender::builder()
+        .from("sender@example.com")
+        .host("127.0.0.1")
+        .port(server.port)
+        .tls(TlsMode::StartTlsOpportunistic)
+        .build()
+        .unwrap();
+    let email = Email::builder()
+        .to("rcpt@example.com")
+        .subject("Maybe TLS")
+        .text("body")
+        .build()
+        .unwrap();
+
+    sender.deliver_async(&email).await.unwrap();
+    assert_eq!(server.messages().len(), 1);
+}
+
+#[test]
+fn starttls_required_fails_without_support() {
+    let server = MockSmtp::start();
+    let sender = EmailSender::builder()
+        .from("sender@example.com")
+        .host("127.0.0.1")
+        .port(server.port)
+        .tls(TlsMode::StartTlsRequired)
+        .build()
+        .unwrap();
+    let email = Email::builder().to("rcpt@example.com").subject("x").build().unwrap();
+
+    assert!(sender.deliver(&email).is_err());
+    assert!(server.messages().is_empty());
+}
+
+#[test]
+fn invalid_root_certificate_is_reported() {
+    let res = EmailSender::builder()
+        .from("sender@example.com")
+        .host("smtp.example.com")
+        .add_root_certificate("not a certificate")
+        .build();
+    assert!(res.is_err());
+}
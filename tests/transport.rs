@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{StubTransport, sender};
+use mailkit::transport::{FileTransport, TransportConfig};
+use mailkit::Email;
+
+fn email() -> Email {
+    Email::builder()
+        .to("rcpt@example.com")
+        .bcc("hidden@example.com")
+        .subject("Transport")
+        .text("body")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn custom_transport_receives_envelope() {
+    let transport = Arc::new(StubTransport::new());
+    let sender = sender(transport.clone());
+
+    sender.deliver(&email()).unwrap();
+    assert_eq!(transport.attempted(), ["rcpt@example.com", "hidden@example.com"]);
+}
+
+#[tokio::test]
+async fn file_transport_writes_eml() {
+    let dir = std::env::temp_dir().join(format!("mailkit-file-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sender = sender(FileTransport::new(&dir));
+
+    sender.deliver(&email()).unwrap();
+    sender.deliver_async(&email()).await.unwrap();
+
+    let mut files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+    files.sort();
+    assert_eq!(files.len(), 4);
+    let eml: Vec<_> = files.iter().filter(|p| p.extension().unwrap() == "eml").collect();
+    assert_eq!(eml.len(), 2);
+    let raw = std::fs::read_to_string(eml[0]).unwrap();
+    assert!(raw.contains("Subject: Transport"));
+
+    let sidecar = std::fs::read_to_string(eml[0].with_extension("json")).unwrap();
+    assert_eq!(
+        sidecar,
+        r#"{"from":"sender@example.com","to":["rcpt@example.com","hidden@example.com"]}"#
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn maildir_transport_delivers_to_new() {
+    let dir = std::env::temp_dir().join(format!("mailkit-maildir-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sender = sender(FileTransport::maildir(&dir));
+
+    sender.deliver(&email()).unwrap();
+    sender.deliver_async(&email()).await.unwrap();
+
+    let count = |sub: &str| std::fs::read_dir(dir.join(sub)).unwrap().count();
+    assert_eq!(count("new"), 2);
+    assert_eq!(count("tmp"), 0);
+    assert_eq!(count("cur"), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parse_transport_config() {
+    assert_eq!("smtp".parse::<TransportConfig>().unwrap(), TransportConfig::Smtp);
+    assert_eq!(
+        "file:/tmp/mail".parse::<TransportConfig>().unwrap(),
+        TransportConfig::File("/tmp/mail".into())
+    );
+    assert_eq!(
+        "Maildir:./mail".parse::<TransportConfig>().unwrap(),
+        TransportConfig::Maildir("./mail".into())
+    );
+    assert_eq!(
+        "sendmail".parse::<TransportConfig>().unwrap(),
+        TransportConfig::Sendmail("/usr/sbin/sendmail".into())
+    );
+    assert_eq!(
+        "sendmail:/usr/lib/sendmail".parse::<TransportConfig>().unwrap(),
+        TransportConfig::Sendmail("/usr/lib/sendmail".into())
+    );
+    assert!("file:".parse::<TransportConfig>().is_err());
+    assert!("pigeon".parse::<TransportConfig>().is_err());
+}